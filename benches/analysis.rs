@@ -11,12 +11,24 @@
 //!
 
 use anyhow::Error;
-use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
-use gsd::GSDTrajectory;
-use trajedy::frame::Frame;
+use criterion::{criterion_group, criterion_main, BatchSize, BenchmarkId, Criterion};
+use gsd::{GSDFrame, GSDTrajectory};
+use nalgebra::{UnitQuaternion, Vector3};
+use rstar::{DefaultParams, RStarInsertionStrategy, RTreeParams};
+use trajedy::frame::{self, Frame};
 use trajedy::learning::{extract_features, run_training};
-use trajedy::orientational_order;
 use trajedy::voronoi::voronoi_area;
+use trajedy::{
+    hexatic_order, hexatic_order_par, orientational_order, orientational_order_batched,
+    orientational_order_par,
+};
+
+// The lattices shared with the tests, which refer to the crate through the frame module
+#[allow(dead_code)]
+#[path = "../src/test_utils.rs"]
+mod test_utils;
+
+use test_utils::square_lattice;
 
 const TEST_FILE: &str = "trajectory.gsd";
 
@@ -43,9 +55,115 @@ fn bench_order(c: &mut Criterion) -> Result<(), Error> {
         &frame,
         |b, f| b.iter(|| orientational_order(f, 6)),
     );
+    c.bench_with_input(
+        BenchmarkId::new("orientational_order_batched", TEST_FILE),
+        &frame,
+        |b, f| b.iter(|| orientational_order_batched(f, 6)),
+    );
     Ok(())
 }
 
+/// A square lattice of `n` x `n` particles with a range of orientations
+fn large_frame(n: usize) -> Frame {
+    let mut frame = square_lattice(n);
+    for (i, orientation) in frame.orientation.iter_mut().enumerate() {
+        let angle = 2. * (i as f32 * 0.7).sin();
+        *orientation = UnitQuaternion::from_axis_angle(&Vector3::z_axis(), angle);
+    }
+    frame
+}
+
+fn bench_order_parallel(c: &mut Criterion) {
+    let frame = large_frame(300);
+    let size = frame.len();
+    // The serial versions cache the neighbour lists, so each iteration uses a fresh copy of the
+    // frame, which is also done for the parallel versions for a fair comparison.
+    c.bench_with_input(
+        BenchmarkId::new("orientational_order_serial", size),
+        &frame,
+        |b, f| b.iter(|| orientational_order(&f.clone(), 6)),
+    );
+    c.bench_with_input(
+        BenchmarkId::new("orientational_order_par", size),
+        &frame,
+        |b, f| b.iter(|| orientational_order_par(&f.clone(), 6)),
+    );
+    c.bench_with_input(
+        BenchmarkId::new("hexatic_order_serial", size),
+        &frame,
+        |b, f| b.iter(|| hexatic_order(&f.clone(), 6)),
+    );
+    c.bench_with_input(
+        BenchmarkId::new("hexatic_order_par", size),
+        &frame,
+        |b, f| b.iter(|| hexatic_order_par(&f.clone(), 6)),
+    );
+}
+
+fn bench_neighbour_list(c: &mut Criterion) -> Result<(), Error> {
+    let frame: Frame = GSDTrajectory::new(TEST_FILE)?.get_frame(1)?.into();
+    // Both benchmarks start from a fresh copy of the frame, which has an empty cache, with the
+    // copy made outside the timed section. The cached version populates the cache in the setup.
+    c.bench_with_input(
+        BenchmarkId::new("neighbour_list_uncached", TEST_FILE),
+        &frame,
+        |b, f| {
+            b.iter_batched(
+                || f.clone(),
+                |f| f.neighbour_list_n(6),
+                BatchSize::LargeInput,
+            )
+        },
+    );
+    c.bench_with_input(
+        BenchmarkId::new("neighbour_list_cached", TEST_FILE),
+        &frame,
+        |b, f| {
+            b.iter_batched(
+                || {
+                    let f = f.clone();
+                    f.neighbour_list_n(6);
+                    f
+                },
+                |f| f.neighbour_list_n(6),
+                BatchSize::LargeInput,
+            )
+        },
+    );
+    Ok(())
+}
+
+/// Parameters for the neighbour tree with larger nodes than the defaults
+#[derive(Clone, Copy)]
+struct LargeNodes;
+
+impl RTreeParams for LargeNodes {
+    const MIN_SIZE: usize = 8;
+    const MAX_SIZE: usize = 24;
+    const REINSERTION_COUNT: usize = 4;
+    type DefaultInsertionStrategy = RStarInsertionStrategy;
+}
+
+/// Create a large lattice using each set of parameters for the neighbour tree
+fn bench_tree_params(c: &mut Criterion) {
+    let frame = GSDFrame::from(&square_lattice(200));
+    let size = frame.len();
+
+    // Each iteration uses a fresh copy of the frame so the neighbour lists are not cached
+    let default = Frame::<DefaultParams>::from_with_params(frame.clone());
+    c.bench_with_input(
+        BenchmarkId::new("neighbour_list_default_params", size),
+        &default,
+        |b, f| b.iter(|| f.clone().neighbour_list_n(6)),
+    );
+    let large = Frame::<LargeNodes>::from_with_params(frame);
+    c.bench_with_input(
+        BenchmarkId::new("neighbour_list_large_nodes", size),
+        &large,
+        |b, f| b.iter(|| f.clone().neighbour_list_n(6)),
+    );
+}
+
 fn bench_features(c: &mut Criterion) -> Result<(), Error> {
     let frame: Frame = GSDTrajectory::new(TEST_FILE)?.get_frame(1)?.into();
     c.bench_with_input(
@@ -89,6 +207,6 @@ criterion_group! {
 criterion_group! {
     name = analysis;
     config = Criterion::default().sample_size(10);
-    targets = bench_order, bench_features, bench_predict, bench_voronoi
+    targets = bench_order, bench_order_parallel, bench_neighbour_list, bench_tree_params, bench_features, bench_predict, bench_voronoi
 }
 criterion_main!(analysis);
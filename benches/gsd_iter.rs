@@ -29,7 +29,7 @@ fn iterator_step_by(c: &mut Criterion) {
                     .expect("File not found")
                     .step_by(steps)
                     .take(2)
-                    .map(|f| f.timestep)
+                    .map(|f| f.expect("Reading frame failed").timestep)
                     .collect::<Vec<_>>()
             })
         });
@@ -37,5 +37,48 @@ fn iterator_step_by(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(gsd_iter, iterator_step_by);
+fn get_frame(c: &mut Criterion) {
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("gsd");
+    filename.push("tests");
+    filename.push("trajectory.gsd");
+
+    let trj = GSDTrajectory::new(&filename).expect("File not found");
+    let mut group = c.benchmark_group("get_frame");
+    for index in [0, 4, 8].iter() {
+        group.bench_with_input(BenchmarkId::from_parameter(index), index, |b, &index| {
+            b.iter(|| trj.get_frame(index).expect("Reading frame failed"))
+        });
+    }
+    group.finish();
+}
+
+fn read_frame_into(c: &mut Criterion) {
+    let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    filename.push("gsd");
+    filename.push("tests");
+    filename.push("trajectory.gsd");
+
+    let trj = GSDTrajectory::new(&filename).expect("File not found");
+    let mut group = c.benchmark_group("read_all_frames");
+    group.bench_function("allocate", |b| {
+        b.iter(|| {
+            for index in 0..trj.nframes() {
+                trj.get_frame(index).expect("Reading frame failed");
+            }
+        })
+    });
+    group.bench_function("reuse", |b| {
+        let mut frame = trj.get_frame(0).expect("Reading frame failed");
+        b.iter(|| {
+            for index in 0..trj.nframes() {
+                trj.read_frame_into(index, &mut frame)
+                    .expect("Reading frame failed");
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(gsd_iter, iterator_step_by, get_frame, read_frame_into);
 criterion_main!(gsd_iter);
@@ -24,11 +24,15 @@ fn main() {
         .allowlist_type("gsd_index_entry")
         .allowlist_type("gsd_handle")
         .allowlist_function("gsd_open")
+        .allowlist_function("gsd_create_and_open")
         .allowlist_function("gsd_close")
+        .allowlist_function("gsd_write_chunk")
+        .allowlist_function("gsd_end_frame")
         .allowlist_function("gsd_get_nframes")
         .allowlist_function("gsd_read_chunk")
         .allowlist_function("gsd_find_chunk")
         .allowlist_function("gsd_sizeof_type")
+        .allowlist_function("gsd_find_matching_chunk_name")
         .derive_debug(true)
         // Finish the builder and generate the bindings.
         .generate()
@@ -16,7 +16,7 @@ pub type GSDIndexEntry = gsd_index_entry;
 use anyhow::{anyhow, Error};
 use std::convert::TryInto;
 
-enum GSDType {
+pub(crate) enum GSDType {
     UINT8,
     UINT16,
     UINT32,
@@ -29,9 +29,33 @@ enum GSDType {
     Double,
 }
 
+/// Check the size of a type in Rust matches the size from the C implementation
+///
+/// This is only checked in debug builds, catching any changes to the types in the C header which
+/// haven't been reflected in [`GSDType`].
+///
+fn debug_check_size(c_id: usize, rust_size: usize, c_size: u64) {
+    debug_assert_eq!(
+        rust_size as u64, c_size,
+        "The size of the gsd type {} doesn't match the C implementation",
+        c_id
+    );
+}
+
 impl GSDType {
     pub fn new<T: TryInto<usize>>(c_id: T) -> Result<GSDType, Error> {
-        match c_id.try_into().unwrap_or(0) {
+        let c_id = c_id.try_into().unwrap_or(0);
+        let value = GSDType::from_id(c_id)?;
+        if cfg!(debug_assertions) {
+            debug_check_size(c_id, value.size(), unsafe {
+                gsd_sizeof_type(c_id as gsd_type)
+            });
+        }
+        Ok(value)
+    }
+
+    fn from_id(c_id: usize) -> Result<GSDType, Error> {
+        match c_id {
             0 => Err(anyhow!("The type 0 is an error type")),
             1 => Ok(GSDType::UINT8),
             2 => Ok(GSDType::UINT16),
@@ -88,4 +112,11 @@ mod tests {
             assert_eq!(rust_ver, c_ver);
         }
     }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "doesn't match the C implementation")]
+    fn gsd_type_size_mismatch() {
+        debug_check_size(9, 8, unsafe { gsd_sizeof_type(9) });
+    }
 }
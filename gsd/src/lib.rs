@@ -6,9 +6,13 @@
 
 use anyhow::{anyhow, bail, Error};
 use std::cell::UnsafeCell;
-use std::ffi::{c_void, CString};
+use std::ffi::{c_void, CStr, CString};
+use std::io::Write;
 use std::mem::MaybeUninit;
-use std::path::Path;
+use std::ops::{Bound, RangeBounds};
+use std::os::raw::c_char;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 mod gsd_bindings;
 
@@ -20,7 +24,19 @@ pub struct GSDFrame {
     pub position: Vec<[f32; 3]>,
     pub orientation: Vec<[f32; 4]>,
     pub image: Vec<[i32; 3]>,
+    pub diameter: Vec<f32>,
+    pub charge: Vec<f32>,
+    pub moment_inertia: Vec<[f32; 3]>,
+    pub angmom: Vec<[f32; 4]>,
+    /// The index of the type of each particle
+    pub typeid: Vec<u32>,
+    /// The rigid body each particle belongs to, which is -1 for particles not in a body
+    pub body: Vec<i32>,
     pub simulation_cell: [f32; 6],
+    /// The number of dimensions of the simulation, when it is recorded in the file
+    pub dimensions: Option<u8>,
+    /// The indices of the pair of particles in each bond
+    pub bonds: Vec<[u32; 2]>,
 }
 
 impl GSDFrame {
@@ -28,12 +44,79 @@ impl GSDFrame {
         GSDFrame {
             timestep: 0,
             position: vec![[0.; 3]; n],
-            orientation: vec![[0.; 4]; n],
+            // These are the defaults hoomd uses when the chunks are not present, with the
+            // orientation being the identity quaternion
+            orientation: vec![[1., 0., 0., 0.]; n],
             image: vec![[0; 3]; n],
+            diameter: vec![1.; n],
+            charge: vec![0.; n],
+            moment_inertia: vec![[0.; 3]; n],
+            angmom: vec![[0.; 4]; n],
+            typeid: vec![0; n],
+            body: vec![-1; n],
             simulation_cell: [0.; 6],
+            dimensions: None,
+            bonds: Vec::new(),
         }
     }
 
+    /// Create a frame from the positions and orientations of the particles
+    ///
+    /// The remaining values take the defaults used by hoomd, with the timestep being 0.
+    ///
+    /// # Panics
+    ///
+    /// When the number of positions and orientations are different.
+    ///
+    pub fn from_arrays(
+        position: Vec<[f32; 3]>,
+        orientation: Vec<[f32; 4]>,
+        simulation_cell: [f32; 6],
+    ) -> GSDFrame {
+        assert_eq!(
+            position.len(),
+            orientation.len(),
+            "There needs to be an orientation for each position"
+        );
+        let n = position.len();
+        GSDFrame {
+            timestep: 0,
+            position,
+            orientation,
+            image: vec![[0; 3]; n],
+            diameter: vec![1.; n],
+            charge: vec![0.; n],
+            moment_inertia: vec![[0.; 3]; n],
+            angmom: vec![[0.; 4]; n],
+            typeid: vec![0; n],
+            body: vec![-1; n],
+            simulation_cell,
+            dimensions: None,
+            bonds: Vec::new(),
+        }
+    }
+
+    /// Set every value to the default for a frame with `n` particles, keeping the allocations
+    fn reset(&mut self, n: usize) {
+        fn fill<T: Clone>(values: &mut Vec<T>, n: usize, value: T) {
+            values.clear();
+            values.resize(n, value);
+        }
+        self.timestep = 0;
+        fill(&mut self.position, n, [0.; 3]);
+        fill(&mut self.orientation, n, [1., 0., 0., 0.]);
+        fill(&mut self.image, n, [0; 3]);
+        fill(&mut self.diameter, n, 1.);
+        fill(&mut self.charge, n, 0.);
+        fill(&mut self.moment_inertia, n, [0.; 3]);
+        fill(&mut self.angmom, n, [0.; 4]);
+        fill(&mut self.typeid, n, 0);
+        fill(&mut self.body, n, -1);
+        self.simulation_cell = [0.; 6];
+        self.dimensions = None;
+        self.bonds.clear();
+    }
+
     pub fn len(&self) -> usize {
         self.position.len()
     }
@@ -43,6 +126,37 @@ impl GSDFrame {
     }
 }
 
+/// A description of the contents of a GSD file, which is useful for diagnosing unfamiliar files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TrajectoryInfo {
+    /// The version of the GSD file format as `major.minor`
+    pub gsd_version: String,
+    /// The name and version of the application which created the file
+    pub application: String,
+    /// The schema describing the meaning of the chunks, which is `hoomd` for simulations
+    pub schema: String,
+    /// The version of the schema as `major.minor`
+    pub schema_version: String,
+    pub nframes: u64,
+    /// The names of the chunks present in the first frame
+    pub chunks: Vec<String>,
+}
+
+/// Format a version number, which stores the major version in the upper 16 bits
+fn format_version(version: u32) -> String {
+    format!("{}.{}", version >> 16, version & 0xffff)
+}
+
+/// Convert a fixed size string from the header of the file, which may not be nul terminated
+fn header_string(value: &[c_char]) -> String {
+    let bytes: Vec<u8> = value
+        .iter()
+        .take_while(|&&c| c != 0)
+        .map(|&c| c as u8)
+        .collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
 /// A handle to a GSD Trajectory allowing interaction
 ///
 /// This provides a handle to interact with a GSD file, providing utilties to read individual
@@ -54,8 +168,13 @@ pub struct GSDTrajectory {
     // sensible for this use case. Additionally it doesn't support Sync so handling a trajecotry
     // in multiple threads is currently unsupported.
     file_handle: UnsafeCell<GSDHandle>,
+    // The temporary file created by from_bytes, which is removed when the trajectory is dropped
+    temporary: Option<PathBuf>,
 }
 
+/// The number of temporary files created by this process, giving each a unique name
+static TEMPORARY_FILES: AtomicUsize = AtomicUsize::new(0);
+
 impl GSDTrajectory {
     pub fn new<P: AsRef<Path>>(filename: P) -> Result<GSDTrajectory, Error> {
         let fname = CString::new(
@@ -88,13 +207,113 @@ impl GSDTrajectory {
         Ok(GSDTrajectory {
             curr: 0,
             file_handle: UnsafeCell::new(handle),
+            temporary: None,
         })
     }
 
+    /// Open a trajectory from the contents of a GSD file held in memory
+    ///
+    /// The GSD library is only able to read from a file, so the contents are written to a
+    /// temporary file, which is removed when the trajectory is dropped. This requires there to be
+    /// space in the temporary directory for a copy of the data, and the file will remain should
+    /// the process exit without the trajectory being dropped.
+    ///
+    pub fn from_bytes(data: &[u8]) -> Result<GSDTrajectory, Error> {
+        let (filename, mut file) = create_temporary_file()?;
+        let written = file.write_all(data);
+        drop(file);
+        let opened = written
+            .map_err(Error::from)
+            .and_then(|_| GSDTrajectory::new(&filename));
+        match opened {
+            Ok(mut trj) => {
+                trj.temporary = Some(filename);
+                Ok(trj)
+            }
+            Err(e) => {
+                // The error opening the file is more useful than any error removing it
+                std::fs::remove_file(&filename).unwrap_or(());
+                Err(e)
+            }
+        }
+    }
+
     pub fn nframes(&self) -> u64 {
         unsafe { gsd_get_nframes(self.file_handle.get()) }
     }
 
+    /// Describe the contents of the file
+    ///
+    /// The chunk names are those present in the first frame, with an empty list for a file
+    /// without any frames.
+    ///
+    pub fn info(&self) -> Result<TrajectoryInfo, Error> {
+        let header = unsafe { &(*self.file_handle.get()).header };
+        let mut info = TrajectoryInfo {
+            gsd_version: format_version(header.gsd_version),
+            application: header_string(&header.application),
+            schema: header_string(&header.schema),
+            schema_version: format_version(header.schema_version),
+            nframes: self.nframes(),
+            chunks: Vec::new(),
+        };
+        if info.nframes == 0 {
+            return Ok(info);
+        }
+
+        // The names are for every chunk in the file, so only those within the first frame are kept
+        let empty = CString::new("")?;
+        let mut prev: *const c_char = std::ptr::null();
+        loop {
+            prev = unsafe {
+                gsd_find_matching_chunk_name(self.file_handle.get(), empty.as_ptr(), prev)
+            };
+            if prev.is_null() {
+                break;
+            }
+            let name = unsafe { CStr::from_ptr(prev) }.to_str()?;
+            if self._safe_gsd_find_chunk(0, name).is_ok() {
+                info.chunks.push(name.to_string());
+            }
+        }
+        Ok(info)
+    }
+
+    /// Read the frames within a range of indices
+    ///
+    /// The range is clamped to the frames within the trajectory, so `trj.slice(5..)` reads from
+    /// the sixth frame until the end of the trajectory.
+    ///
+    pub fn slice<R: RangeBounds<u64>>(
+        &self,
+        range: R,
+    ) -> impl Iterator<Item = Result<GSDFrame, Error>> + '_ {
+        let start = match range.start_bound() {
+            Bound::Included(&s) => s,
+            Bound::Excluded(&s) => s.saturating_add(1),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(&e) => e.saturating_add(1),
+            Bound::Excluded(&e) => e,
+            Bound::Unbounded => u64::MAX,
+        }
+        .min(self.nframes());
+        (start..end).map(move |index| self.get_frame(index))
+    }
+
+    /// Ensure the index of a frame is within the trajectory
+    fn check_frame(&self, index: u64) -> Result<(), Error> {
+        if index >= self.nframes() {
+            bail!(
+                "Frame {} is out of range for a trajectory of {} frames",
+                index,
+                self.nframes()
+            );
+        }
+        Ok(())
+    }
+
     fn _safe_gsd_find_chunk(&self, frame: u64, name: &str) -> Result<GSDIndexEntry, Error> {
         let c_name = CString::new(name)?;
         unsafe { gsd_find_chunk(self.file_handle.get(), frame, c_name.as_ptr()).as_ref() }
@@ -102,12 +321,61 @@ impl GSDTrajectory {
             .ok_or_else(|| anyhow!("Chunk '{}' was not found", name))
     }
 
-    fn read_chunk<T: Sized>(&self, index: u64, name: &str, chunk: &mut [T]) -> Result<(), Error> {
-        let gsd_index = match self._safe_gsd_find_chunk(index, name) {
-            Ok(g) => g,
-            Err(_) => return Ok(()),
-        };
+    /// Read a chunk which may not be present in the frame, leaving the values unchanged if absent
+    ///
+    /// This returns whether the chunk was present in the frame.
+    ///
+    fn read_optional_chunk<T: Sized>(
+        &self,
+        frame: u64,
+        name: &str,
+        chunk: &mut [T],
+    ) -> Result<bool, Error> {
+        match self._safe_gsd_find_chunk(frame, name) {
+            Ok(gsd_index) => self.read_entry(&gsd_index, name, chunk).map(|_| true),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Whether the frame at `index` contains the `configuration/step` chunk
+    fn has_step(&self, index: u64) -> bool {
+        self._safe_gsd_find_chunk(index, "configuration/step")
+            .is_ok()
+    }
+
+    /// Read a chunk which may not be present, taking the values from the first frame when absent
+    ///
+    /// Hoomd only writes the chunks which don't change between frames to the first frame, which
+    /// gives the values for the following frames. This returns whether the chunk was present in
+    /// either frame, leaving the values unchanged otherwise.
+    ///
+    fn read_chunk_or_first<T: Sized>(
+        &self,
+        index: u64,
+        name: &str,
+        chunk: &mut [T],
+    ) -> Result<bool, Error> {
+        for &frame in [index, 0].iter() {
+            if self.read_optional_chunk(frame, name, chunk)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
 
+    fn read_chunk<T: Sized>(&self, frame: u64, name: &str, chunk: &mut [T]) -> Result<(), Error> {
+        let gsd_index = self
+            ._safe_gsd_find_chunk(frame, name)
+            .map_err(|_| anyhow!("Required chunk '{}' was not found", name))?;
+        self.read_entry(&gsd_index, name, chunk)
+    }
+
+    fn read_entry<T: Sized>(
+        &self,
+        gsd_index: &GSDIndexEntry,
+        name: &str,
+        chunk: &mut [T],
+    ) -> Result<(), Error> {
         // This checks that we are going to read the input correctly and produces a useful error
         // message should there be a mismatch of sizes.
         if gsd_index.expected_size()? != chunk.len() * std::mem::size_of::<T>() {
@@ -129,7 +397,7 @@ impl GSDTrajectory {
             gsd_read_chunk(
                 self.file_handle.get(),
                 chunk as *mut [T] as *mut c_void,
-                &gsd_index as *const GSDIndexEntry,
+                gsd_index as *const GSDIndexEntry,
             )
         };
 
@@ -141,45 +409,276 @@ impl GSDTrajectory {
         }
     }
 
+    fn read_values<T: Clone + Default>(
+        &self,
+        gsd_index: &GSDIndexEntry,
+        name: &str,
+    ) -> Result<Vec<T>, Error> {
+        let mut values = vec![T::default(); gsd_index.N as usize * gsd_index.M as usize];
+        self.read_entry(gsd_index, name, &mut values)?;
+        Ok(values)
+    }
+
+    /// Read any chunk within a frame, converting the values to double precision
+    ///
+    /// This returns `None` when the chunk is not present in the frame.
+    ///
+    fn read_named_chunk(&self, frame: u64, name: &str) -> Result<Option<Vec<f64>>, Error> {
+        self.check_frame(frame)?;
+        let gsd_index = match self._safe_gsd_find_chunk(frame, name) {
+            Ok(gsd_index) => gsd_index,
+            Err(_) => return Ok(None),
+        };
+        let values: Vec<f64> = match GSDType::new(gsd_index.type_)? {
+            GSDType::UINT8 => convert(self.read_values::<u8>(&gsd_index, name)?),
+            GSDType::UINT16 => convert(self.read_values::<u16>(&gsd_index, name)?),
+            GSDType::UINT32 => convert(self.read_values::<u32>(&gsd_index, name)?),
+            GSDType::INT8 => convert(self.read_values::<i8>(&gsd_index, name)?),
+            GSDType::INT16 => convert(self.read_values::<i16>(&gsd_index, name)?),
+            GSDType::INT32 => convert(self.read_values::<i32>(&gsd_index, name)?),
+            GSDType::Float => convert(self.read_values::<f32>(&gsd_index, name)?),
+            GSDType::Double => self.read_values::<f64>(&gsd_index, name)?,
+            // There is no lossless conversion of 64 bit integers
+            GSDType::UINT64 => self
+                .read_values::<u64>(&gsd_index, name)?
+                .into_iter()
+                .map(|v| v as f64)
+                .collect(),
+            GSDType::INT64 => self
+                .read_values::<i64>(&gsd_index, name)?
+                .into_iter()
+                .map(|v| v as f64)
+                .collect(),
+        };
+        Ok(Some(values))
+    }
+
+    /// Read the simulation cell of every frame in the trajectory
+    ///
+    /// This only reads the `configuration/box` chunk of each frame, which is much faster than
+    /// reading every frame when only the dimensions of the cell are required.
+    ///
+    pub fn all_boxes(&self) -> Result<Vec<[f32; 6]>, Error> {
+        (0..self.nframes())
+            .map(|frame| {
+                let name = "configuration/box";
+                let gsd_index = self
+                    ._safe_gsd_find_chunk(frame, name)
+                    .map_err(|_| anyhow!("Required chunk '{}' was not found", name))?;
+                let mut simulation_cell = [0.; 6];
+                self.read_entry(&gsd_index, name, &mut simulation_cell)?;
+                Ok(simulation_cell)
+            })
+            .collect()
+    }
+
+    /// Read a scalar quantity logged within the `log/` namespace of a frame
+    ///
+    /// The `name` excludes the namespace, so the kinetic energy stored in the chunk
+    /// `log/kinetic_energy` is read using `trj.read_log_scalar(0, "kinetic_energy")`. This returns
+    /// `None` when the quantity was not logged for the frame.
+    ///
+    pub fn read_log_scalar(&self, frame: u64, name: &str) -> Result<Option<f64>, Error> {
+        let chunk = format!("log/{}", name);
+        match self.read_named_chunk(frame, &chunk)? {
+            Some(values) if values.len() == 1 => Ok(Some(values[0])),
+            Some(values) => bail!(
+                "The chunk '{}' is not a scalar, having {} values",
+                chunk,
+                values.len()
+            ),
+            None => Ok(None),
+        }
+    }
+
+    /// Read a per-particle quantity logged within the `log/` namespace of a frame
+    ///
+    /// This is the same as [`GSDTrajectory::read_log_scalar`], returning all the values within
+    /// the chunk.
+    ///
+    pub fn read_log_vector(&self, frame: u64, name: &str) -> Result<Option<Vec<f64>>, Error> {
+        self.read_named_chunk(frame, &format!("log/{}", name))
+    }
+
+    /// Read a chunk with a value for each particle, like `log/particles/stress`, as a column
+    ///
+    /// Each value of type `T` contains all the columns of a row within the chunk, so a chunk with
+    /// 3 floating point values for each particle is read using `[f32; 3]`. This returns an error
+    /// when the chunk doesn't have a row for each of the particles in the frame, or where the size
+    /// of `T` doesn't match the size of a row.
+    ///
+    pub fn read_particle_column<T: Copy + Default>(
+        &self,
+        frame: u64,
+        name: &str,
+    ) -> Result<Vec<T>, Error> {
+        self.check_frame(frame)?;
+        let num_particles = self.read_values::<u32>(
+            &self._safe_gsd_find_chunk(frame, "particles/N")?,
+            "particles/N",
+        )?[0] as u64;
+        let gsd_index = self._safe_gsd_find_chunk(frame, name)?;
+        if gsd_index.N != num_particles {
+            bail!(
+                "The chunk '{}' has {} rows, while the frame has {} particles",
+                name,
+                gsd_index.N,
+                num_particles
+            );
+        }
+        let mut values = vec![T::default(); num_particles as usize];
+        self.read_entry(&gsd_index, name, &mut values)?;
+        Ok(values)
+    }
+
+    /// Read the number of dimensions of a frame
+    ///
+    /// Hoomd only writes the dimensions to the first frame, which then apply to every frame, so
+    /// where the frame doesn't contain the dimensions the value from the first frame is used.
+    ///
+    fn read_dimensions(&self, index: u64) -> Result<Option<u8>, Error> {
+        let name = "configuration/dimensions";
+        for &frame in [index, 0].iter() {
+            if let Ok(gsd_index) = self._safe_gsd_find_chunk(frame, name) {
+                return Ok(Some(self.read_values::<u8>(&gsd_index, name)?[0]));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read the pairs of particles which are bonded within a frame
+    ///
+    /// Like the dimensions, the bonds which are not present in a frame are taken from the first
+    /// frame, as hoomd only writes the bonds when they differ from the first frame.
+    ///
+    fn read_bonds(&self, index: u64, bonds: &mut Vec<[u32; 2]>) -> Result<(), Error> {
+        bonds.clear();
+        for &frame in [index, 0].iter() {
+            if let Ok(gsd_index) = self._safe_gsd_find_chunk(frame, "bonds/N") {
+                let num_bonds = self.read_values::<u32>(&gsd_index, "bonds/N")?[0] as usize;
+                // A frame without any bonds has no group chunk
+                bonds.resize(num_bonds, [0; 2]);
+                if num_bonds > 0 {
+                    let gsd_index = self._safe_gsd_find_chunk(frame, "bonds/group")?;
+                    self.read_entry(&gsd_index, "bonds/group", bonds)?;
+                }
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+
     pub fn get_frame(&self, index: u64) -> Result<GSDFrame, Error> {
+        let mut frame = GSDFrame::new(0);
+        self.read_frame_into(index, &mut frame)?;
+        Ok(frame)
+    }
+
+    /// Read a frame, reusing the memory already allocated by `frame`
+    ///
+    /// This gives the same values as [`GSDTrajectory::get_frame`], although when reading many
+    /// frames it avoids allocating the arrays for each frame, only growing the arrays when a frame
+    /// has more particles. Where there is an error, the contents of `frame` are unspecified.
+    ///
+    /// A trajectory without `configuration/step` uses the index of each frame as the timestep,
+    /// while one which has the timestep in only some of the frames after the first is an error.
+    ///
+    pub fn read_frame_into(&self, index: u64, frame: &mut GSDFrame) -> Result<(), Error> {
+        self.check_frame(index)?;
+
+        // These are required components
         let mut num_particles = [0_u32; 1];
         self.read_chunk(index, "particles/N", &mut num_particles)?;
-        let mut frame = GSDFrame::new(num_particles[0] as usize);
-        let mut timestep = [0_u64; 1];
-        self.read_chunk(index, "configuration/step", &mut timestep)?;
-        frame.timestep = timestep[0];
-        // These are required components
+        frame.reset(num_particles[0] as usize);
         self.read_chunk(index, "configuration/box", &mut frame.simulation_cell)?;
-        self.read_chunk(index, "particles/orientation", &mut frame.orientation)?;
         self.read_chunk(index, "particles/position", &mut frame.position)?;
 
-        // These are optional components
+        // These are optional components, although they have to be the correct size when present.
+        // Without the timestep, the index of the frame is used so each frame remains distinct.
+        let mut timestep = [index; 1];
+        let has_step = self.read_optional_chunk(index, "configuration/step", &mut timestep)?;
+        // The first frame is exempt, since hoomd omits chunks with their default value there.
+        if index > 0 && has_step != self.has_step(self.nframes() - 1) {
+            bail!(
+                "Frame {} differs from the last frame in whether it has a timestep",
+                index
+            );
+        }
+        frame.timestep = timestep[0];
+        self.read_optional_chunk(index, "particles/orientation", &mut frame.orientation)?;
+
+        // These are optional components which hoomd only writes to the first frame when they
+        // don't change
+        self.read_chunk_or_first(index, "particles/typeid", &mut frame.typeid)?;
+        self.read_chunk_or_first(index, "particles/body", &mut frame.body)?;
+        self.read_chunk_or_first(index, "particles/diameter", &mut frame.diameter)?;
+        self.read_chunk_or_first(index, "particles/charge", &mut frame.charge)?;
+        self.read_chunk_or_first(index, "particles/moment_inertia", &mut frame.moment_inertia)?;
+        self.read_chunk_or_first(index, "particles/angmom", &mut frame.angmom)?;
+
+        // These are optional components with any errors ignored
         self.read_chunk(index, "particles/image", &mut frame.image)
             .unwrap_or(());
+        frame.dimensions = self.read_dimensions(index)?;
+        self.read_bonds(index, &mut frame.bonds)?;
 
-        Ok(frame)
+        Ok(())
+    }
+}
+
+fn convert<T: Into<f64>>(values: Vec<T>) -> Vec<f64> {
+    values.into_iter().map(Into::into).collect()
+}
+
+/// Create a new file in the temporary directory which is unique to this process
+///
+/// The file is only created when it doesn't already exist, so an existing file or symlink at the
+/// same path is never overwritten, with the next name tried instead.
+///
+fn create_temporary_file() -> Result<(PathBuf, std::fs::File), Error> {
+    loop {
+        let mut filename = std::env::temp_dir();
+        filename.push(format!(
+            "gsd-{}-{}.gsd",
+            std::process::id(),
+            TEMPORARY_FILES.fetch_add(1, Ordering::Relaxed)
+        ));
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&filename)
+        {
+            Ok(file) => return Ok((filename, file)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
     }
 }
 
 impl Drop for GSDTrajectory {
     fn drop(&mut self) {
         unsafe { gsd_close(self.file_handle.get()) };
+        if let Some(filename) = &self.temporary {
+            // There is nothing to be done when the file can't be removed
+            std::fs::remove_file(filename).unwrap_or(());
+        }
     }
 }
 
+/// Iterate over the frames of the trajectory, giving the error for any frame which can't be read
+///
+/// The iteration continues after a frame which can't be read, with the following frames being
+/// read as normal.
+///
 impl<'a> Iterator for GSDTrajectory {
-    type Item = GSDFrame;
+    type Item = Result<GSDFrame, Error>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.curr += 1;
-        match self.get_frame(self.curr - 1) {
-            Ok(frame) => Some(frame),
-            Err(_) if self.curr >= self.nframes() => None,
-            Err(e) => {
-                println!("{}", e);
-                None
-            }
+        if self.curr >= self.nframes() {
+            return None;
         }
+        self.curr += 1;
+        Some(self.get_frame(self.curr - 1))
     }
 
     fn size_hint(&self) -> (usize, Option<usize>) {
@@ -187,29 +686,646 @@ impl<'a> Iterator for GSDTrajectory {
     }
 
     fn nth(&mut self, n: usize) -> Option<Self::Item> {
-        self.curr += 1 + n as u64;
-        match self.get_frame(self.curr - 1) {
-            Ok(frame) => Some(frame),
-            Err(_) if self.curr >= self.nframes() => None,
-            Err(e) => {
-                println!("{}", e);
-                None
-            }
+        self.curr = self.curr.saturating_add(n as u64);
+        self.next()
+    }
+}
+
+/// A handle to write frames to a new GSD file
+///
+/// This creates a file following the hoomd schema, writing all the components of a [`GSDFrame`]
+/// for each frame. The file is finalised when the writer is dropped.
+pub struct GSDWriter {
+    file_handle: GSDHandle,
+}
+
+impl GSDWriter {
+    pub fn create<P: AsRef<Path>>(filename: P) -> Result<GSDWriter, Error> {
+        let fname = CString::new(
+            filename
+                .as_ref()
+                .to_str()
+                .ok_or_else(|| anyhow!("Unable to convert filename to str"))?,
+        )?;
+        let application = CString::new(concat!("trajedy ", env!("CARGO_PKG_VERSION")))?;
+        let schema = CString::new("hoomd")?;
+        let mut handle = MaybeUninit::<GSDHandle>::uninit();
+        let retvalue = unsafe {
+            gsd_create_and_open(
+                handle.as_mut_ptr(),
+                fname.as_ptr(),
+                application.as_ptr(),
+                schema.as_ptr(),
+                // The version 1.4 of the hoomd schema
+                (1 << 16) | 4,
+                gsd_open_flag_GSD_OPEN_APPEND,
+                0,
+            )
+        };
+        if retvalue != 0 {
+            bail!("Unable to create file, error code {}", retvalue);
+        }
+
+        Ok(GSDWriter {
+            // Creating the file succeeded, so the handle is initialised
+            file_handle: unsafe { handle.assume_init() },
+        })
+    }
+
+    fn write_chunk<T: Sized>(
+        &mut self,
+        name: &str,
+        gsd_type: gsd_type,
+        columns: u32,
+        chunk: &[T],
+    ) -> Result<(), Error> {
+        let c_name = CString::new(name)?;
+        let type_size = GSDType::new(gsd_type)?.size();
+        // The number of rows in the chunk, each of which have `columns` values
+        let rows = std::mem::size_of_val(chunk) / (type_size * columns as usize);
+
+        let returnval = unsafe {
+            gsd_write_chunk(
+                &mut self.file_handle,
+                c_name.as_ptr(),
+                gsd_type,
+                rows as u64,
+                columns,
+                0,
+                chunk.as_ptr() as *const c_void,
+            )
+        };
+        match returnval {
+            0 => Ok(()),
+            e => Err(anyhow!(
+                "Unable to write chunk '{}', error code {}",
+                name,
+                e
+            )),
+        }
+    }
+
+    /// Write all the values of a frame to the file as a new frame
+    pub fn write_frame(&mut self, frame: &GSDFrame) -> Result<(), Error> {
+        if let Some(dimensions) = frame.dimensions {
+            self.write_chunk(
+                "configuration/dimensions",
+                gsd_type_GSD_TYPE_UINT8,
+                1,
+                &[dimensions],
+            )?;
+        }
+        self.write_chunk(
+            "configuration/step",
+            gsd_type_GSD_TYPE_UINT64,
+            1,
+            &[frame.timestep],
+        )?;
+        self.write_chunk(
+            "configuration/box",
+            gsd_type_GSD_TYPE_FLOAT,
+            1,
+            &frame.simulation_cell,
+        )?;
+        self.write_chunk(
+            "particles/N",
+            gsd_type_GSD_TYPE_UINT32,
+            1,
+            &[frame.len() as u32],
+        )?;
+        self.write_chunk(
+            "particles/position",
+            gsd_type_GSD_TYPE_FLOAT,
+            3,
+            &frame.position,
+        )?;
+        self.write_chunk(
+            "particles/orientation",
+            gsd_type_GSD_TYPE_FLOAT,
+            4,
+            &frame.orientation,
+        )?;
+        self.write_chunk("particles/image", gsd_type_GSD_TYPE_INT32, 3, &frame.image)?;
+        self.write_chunk(
+            "particles/diameter",
+            gsd_type_GSD_TYPE_FLOAT,
+            1,
+            &frame.diameter,
+        )?;
+        self.write_chunk(
+            "particles/charge",
+            gsd_type_GSD_TYPE_FLOAT,
+            1,
+            &frame.charge,
+        )?;
+        self.write_chunk(
+            "particles/moment_inertia",
+            gsd_type_GSD_TYPE_FLOAT,
+            3,
+            &frame.moment_inertia,
+        )?;
+        self.write_chunk(
+            "particles/angmom",
+            gsd_type_GSD_TYPE_FLOAT,
+            4,
+            &frame.angmom,
+        )?;
+        self.write_chunk(
+            "particles/typeid",
+            gsd_type_GSD_TYPE_UINT32,
+            1,
+            &frame.typeid,
+        )?;
+        self.write_chunk("particles/body", gsd_type_GSD_TYPE_INT32, 1, &frame.body)?;
+        // The number of bonds is always written, so a frame without bonds doesn't take the bonds
+        // of the first frame when read. However, gsd doesn't allow writing an empty chunk.
+        self.write_chunk(
+            "bonds/N",
+            gsd_type_GSD_TYPE_UINT32,
+            1,
+            &[frame.bonds.len() as u32],
+        )?;
+        if !frame.bonds.is_empty() {
+            self.write_chunk("bonds/group", gsd_type_GSD_TYPE_UINT32, 2, &frame.bonds)?;
+        }
+
+        match unsafe { gsd_end_frame(&mut self.file_handle) } {
+            0 => Ok(()),
+            e => Err(anyhow!("Unable to complete frame, error code {}", e)),
         }
     }
 }
 
+impl Drop for GSDWriter {
+    fn drop(&mut self) {
+        unsafe { gsd_close(&mut self.file_handle) };
+    }
+}
+
+/// Write every `skip`-th frame of a trajectory to a new file, starting with the first frame
+///
+/// At most `num_frames` frames are written, with all the sampled frames written when this is
+/// `None`. An error reading any of the frames is returned, rather than writing a truncated file.
+///
+pub fn decimate<P: AsRef<Path>, Q: AsRef<Path>>(
+    input: P,
+    output: Q,
+    skip: usize,
+    num_frames: Option<usize>,
+) -> Result<(), Error> {
+    if skip == 0 {
+        bail!("The number of frames to skip needs to be at least 1");
+    }
+    let trj = GSDTrajectory::new(input)?;
+    let mut writer = GSDWriter::create(output)?;
+    for index in (0..trj.nframes())
+        .step_by(skip)
+        .take(num_frames.unwrap_or(usize::MAX))
+    {
+        writer.write_frame(&trj.get_frame(index)?)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use std::path::PathBuf;
 
-    #[test]
-    fn file_read() {
+    fn test_file(name: &str) -> PathBuf {
         let mut filename = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
         filename.push("tests");
-        filename.push("trajectory.gsd");
+        filename.push(name);
+        filename
+    }
+
+    #[test]
+    fn file_read() {
+        let filename = test_file("trajectory.gsd");
         println!("Filename: {:?}", &filename);
         GSDTrajectory::new(filename).unwrap();
     }
+
+    #[test]
+    fn read_diameter_charge() -> Result<(), Error> {
+        let frame = GSDTrajectory::new(test_file("polydisperse.gsd"))?.get_frame(0)?;
+        assert_eq!(frame.diameter, vec![1.0, 1.2, 0.8, 1.4]);
+        assert_eq!(frame.charge, vec![1., -1., 1., -1.]);
+        Ok(())
+    }
+
+    #[test]
+    fn diameter_charge_from_first_frame() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("first-frame.gsd"))?;
+        // The diameter and charge are only written to the first frame
+        for index in 0..trj.nframes() {
+            let frame = trj.get_frame(index)?;
+            assert_eq!(frame.diameter, vec![1.0, 1.2, 0.8, 1.4]);
+            assert_eq!(frame.charge, vec![1., -1., 1., -1.]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn wrong_size_diameter() -> Result<(), Error> {
+        // There are only 3 values of the diameter for the 4 particles
+        let trj = GSDTrajectory::new(test_file("wrong-diameter.gsd"))?;
+        assert!(trj.get_frame(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn default_diameter_charge() -> Result<(), Error> {
+        let frame = GSDTrajectory::new(test_file("trajectory.gsd"))?.get_frame(1)?;
+        assert_eq!(frame.diameter, vec![1.; frame.len()]);
+        assert_eq!(frame.charge, vec![0.; frame.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_rotational() -> Result<(), Error> {
+        let frame = GSDTrajectory::new(test_file("rotational.gsd"))?.get_frame(0)?;
+        assert_eq!(frame.moment_inertia, vec![[1., 2., 3.], [0.5, 0.5, 1.]]);
+        assert_eq!(frame.angmom, vec![[0., 0., 0., 1.], [0., 0., 0.5, -0.5]]);
+        Ok(())
+    }
+
+    #[test]
+    fn rotational_from_first_frame() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("first-frame.gsd"))?;
+        // The moment of inertia and angular momentum are only written to the first frame
+        for index in 0..trj.nframes() {
+            let frame = trj.get_frame(index)?;
+            assert_eq!(frame.moment_inertia, vec![[1., 2., 3.]; 4]);
+            assert_eq!(frame.angmom, vec![[0., 0., 0., 1.]; 4]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn default_rotational() -> Result<(), Error> {
+        let frame = GSDTrajectory::new(test_file("polydisperse.gsd"))?.get_frame(0)?;
+        assert_eq!(frame.moment_inertia, vec![[0.; 3]; frame.len()]);
+        assert_eq!(frame.angmom, vec![[0.; 4]; frame.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn frame_out_of_range() -> Result<(), Error> {
+        let mut trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let error = trj.get_frame(trj.nframes()).unwrap_err();
+        assert!(error.to_string().contains("out of range"), "{}", error);
+        assert!(trj.get_frame(u64::MAX).is_err());
+
+        assert_eq!(trj.nth(9).transpose()?.map(|f| f.len()), Some(4032));
+        assert!(trj.next().is_none());
+        assert!(trj.nth(20).is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn read_all_boxes() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let boxes = trj.all_boxes()?;
+        assert_eq!(boxes.len() as u64, trj.nframes());
+        assert_eq!(boxes[0], trj.get_frame(0)?.simulation_cell);
+        assert_eq!(boxes[9], trj.get_frame(9)?.simulation_cell);
+        Ok(())
+    }
+
+    #[test]
+    fn trajectory_info() -> Result<(), Error> {
+        let info = GSDTrajectory::new(test_file("trajectory.gsd"))?.info()?;
+        assert_eq!(info.gsd_version, "1.0");
+        assert_eq!(info.application, "gsd.hoomd 1.9.2");
+        assert_eq!(info.schema, "hoomd");
+        assert_eq!(info.schema_version, "1.4");
+        assert_eq!(info.nframes, 10);
+        assert!(info.chunks.iter().any(|c| c == "particles/position"));
+        // The step is only written from the second frame
+        assert!(!info.chunks.iter().any(|c| c == "configuration/step"));
+        Ok(())
+    }
+
+    #[test]
+    fn read_particle_column() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let position: Vec<[f32; 3]> = trj.read_particle_column(1, "particles/position")?;
+        let frame = trj.get_frame(1)?;
+        assert_eq!(position.len(), frame.len());
+        assert_eq!(position, frame.position);
+
+        // The values of each row need to be read together
+        assert!(trj
+            .read_particle_column::<f32>(1, "particles/position")
+            .is_err());
+        // The box is not a per-particle quantity
+        assert!(trj
+            .read_particle_column::<f32>(1, "configuration/box")
+            .is_err());
+        assert!(trj
+            .read_particle_column::<f32>(1, "particles/missing")
+            .is_err());
+
+        let trj = GSDTrajectory::new(test_file("logged.gsd"))?;
+        let energy: Vec<f32> = trj.read_particle_column(0, "log/particles/energy")?;
+        assert_eq!(energy, vec![0.5, -0.25]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_log() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("logged.gsd"))?;
+        assert_eq!(trj.read_log_scalar(0, "kinetic_energy")?, Some(1.25));
+        assert_eq!(
+            trj.read_log_vector(0, "particles/energy")?,
+            Some(vec![0.5, -0.25])
+        );
+        assert!(trj.read_log_scalar(0, "particles/energy").is_err());
+        assert!(trj.read_log_scalar(1, "kinetic_energy").is_err());
+
+        let trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        assert_eq!(trj.read_log_scalar(1, "kinetic_energy")?, None);
+        assert_eq!(trj.read_log_vector(1, "particles/energy")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn slice_ranges() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let timesteps = |frames: Vec<Result<GSDFrame, Error>>| -> Result<Vec<u64>, Error> {
+            frames.into_iter().map(|f| Ok(f?.timestep)).collect()
+        };
+        let all = timesteps(trj.slice(..).collect())?;
+        assert_eq!(all.len() as u64, trj.nframes());
+
+        assert_eq!(timesteps(trj.slice(..5).collect())?, all[..5]);
+        assert_eq!(timesteps(trj.slice(3..).collect())?, all[3..]);
+        assert_eq!(timesteps(trj.slice(2..=4).collect())?, all[2..=4]);
+        // Ranges extending past the end of the trajectory are clamped
+        assert_eq!(timesteps(trj.slice(8..100).collect())?, all[8..]);
+        Ok(())
+    }
+
+    #[test]
+    fn from_arrays() {
+        let frame = GSDFrame::from_arrays(
+            vec![[0.; 3], [1., 0., 0.]],
+            vec![[1., 0., 0., 0.]; 2],
+            [4., 4., 1., 0., 0., 0.],
+        );
+        assert_eq!(frame.len(), 2);
+        assert_eq!(frame.timestep, 0);
+        assert_eq!(frame.image, vec![[0; 3]; 2]);
+        assert_eq!(frame.diameter, vec![1.; 2]);
+    }
+
+    #[test]
+    fn from_bytes() -> Result<(), Error> {
+        let data = std::fs::read(test_file("trajectory.gsd"))?;
+        let trj = GSDTrajectory::from_bytes(&data)?;
+        let expected = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        assert_eq!(trj.nframes(), expected.nframes());
+        assert_eq!(trj.get_frame(1)?.position, expected.get_frame(1)?.position);
+
+        // The temporary file is removed with the trajectory
+        let filename = trj
+            .temporary
+            .clone()
+            .expect("No temporary file was created");
+        assert!(filename.exists());
+        drop(trj);
+        assert!(!filename.exists());
+        Ok(())
+    }
+
+    #[test]
+    fn from_bytes_invalid() {
+        assert!(GSDTrajectory::from_bytes(b"not a gsd file").is_err());
+    }
+
+    #[test]
+    fn from_bytes_existing_file() -> Result<(), Error> {
+        // Files already at the next temporary paths are left untouched
+        let next = TEMPORARY_FILES.load(Ordering::Relaxed);
+        let existing: Vec<PathBuf> = (next..next + 3)
+            .map(|index| {
+                let mut filename = std::env::temp_dir();
+                filename.push(format!("gsd-{}-{}.gsd", std::process::id(), index));
+                filename
+            })
+            .collect();
+        for filename in existing.iter() {
+            std::fs::write(filename, "existing")?;
+        }
+        let data = std::fs::read(test_file("trajectory.gsd"))?;
+        let trj = GSDTrajectory::from_bytes(&data)?;
+        assert_eq!(trj.nframes(), 10);
+        for filename in existing.iter() {
+            assert_eq!(std::fs::read_to_string(filename)?, "existing");
+            std::fs::remove_file(filename)?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn read_frame_into() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let mut frame = trj.get_frame(0)?;
+        for index in 0..trj.nframes() {
+            trj.read_frame_into(index, &mut frame)?;
+            let expected = trj.get_frame(index)?;
+            assert_eq!(frame.timestep, expected.timestep);
+            assert_eq!(frame.position, expected.position);
+            assert_eq!(frame.orientation, expected.orientation);
+            assert_eq!(frame.image, expected.image);
+            assert_eq!(frame.body, expected.body);
+            assert_eq!(frame.simulation_cell, expected.simulation_cell);
+            assert_eq!(frame.dimensions, expected.dimensions);
+        }
+
+        // Reading a frame with a different number of particles resizes the arrays
+        let trj = GSDTrajectory::new(test_file("rigid.gsd"))?;
+        trj.read_frame_into(0, &mut frame)?;
+        assert_eq!(frame.len(), 6);
+        assert_eq!(frame.body, trj.get_frame(0)?.body);
+        assert_eq!(frame.image, vec![[0; 3]; 6]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_body() -> Result<(), Error> {
+        let frame = GSDTrajectory::new(test_file("rigid.gsd"))?.get_frame(0)?;
+        assert_eq!(frame.body, vec![0, 0, 0, 3, 3, -1]);
+        // Each rigid molecule is labelled by the index of the central particle
+        let frame = GSDTrajectory::new(test_file("trajectory.gsd"))?.get_frame(0)?;
+        assert_eq!(frame.body[..4], [0, 1, 2, 3]);
+        // Without the chunk, none of the particles are within a body
+        let frame = GSDTrajectory::new(test_file("logged.gsd"))?.get_frame(0)?;
+        assert_eq!(frame.body, vec![-1; frame.len()]);
+        Ok(())
+    }
+
+    #[test]
+    fn read_dimensions() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        // The dimensions are only stored in the first frame
+        assert_eq!(trj.get_frame(0)?.dimensions, Some(2));
+        assert_eq!(trj.get_frame(3)?.dimensions, Some(2));
+        let trj = GSDTrajectory::new(test_file("no-step.gsd"))?;
+        assert_eq!(trj.get_frame(1)?.dimensions, None);
+        Ok(())
+    }
+
+    #[test]
+    fn typeid_from_first_frame() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("first-frame.gsd"))?;
+        assert_eq!(trj.get_frame(0)?.typeid, vec![0, 1, 0, 1]);
+        // The types are only written to the first frame
+        assert_eq!(trj.get_frame(1)?.typeid, vec![0, 1, 0, 1]);
+        Ok(())
+    }
+
+    #[test]
+    fn body_from_first_frame() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("first-frame.gsd"))?;
+        // The bodies are only written to the first frame
+        for index in 0..trj.nframes() {
+            assert_eq!(trj.get_frame(index)?.body, vec![-1, 1, 1, -1]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn read_bonds() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("bonded.gsd"))?;
+        assert_eq!(trj.get_frame(0)?.bonds, vec![[0, 1], [2, 3]]);
+        // The second frame uses the bonds from the first
+        assert_eq!(trj.get_frame(1)?.bonds, vec![[0, 1], [2, 3]]);
+        let frame = GSDTrajectory::new(test_file("trajectory.gsd"))?.get_frame(0)?;
+        assert!(frame.bonds.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn missing_step() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("no-step.gsd"))?;
+        let timesteps: Vec<u64> = trj
+            .map(|frame| frame.map(|f| f.timestep))
+            .collect::<Result<_, _>>()?;
+        assert_eq!(timesteps, vec![0, 1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_step() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("mixed-step.gsd"))?;
+        assert_eq!(trj.get_frame(0)?.timestep, 0);
+        assert!(trj.get_frame(1).is_err());
+        assert_eq!(trj.get_frame(2)?.timestep, 200);
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_unreadable_frame() -> Result<(), Error> {
+        // The second frame is missing the timestep, while the others have one
+        let frames: Vec<bool> = GSDTrajectory::new(test_file("mixed-step.gsd"))?
+            .map(|frame| frame.is_ok())
+            .collect();
+        assert_eq!(frames, vec![true, false, true]);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_orientation() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("no-orientation.gsd"))?;
+        let mut frame = trj.get_frame(0)?;
+        assert_eq!(frame.orientation, vec![[1., 0., 0., 0.]; 3]);
+        // Reusing a frame also resets the orientations to the identity
+        frame.orientation[1] = [0., 0., 0., 1.];
+        trj.read_frame_into(0, &mut frame)?;
+        assert_eq!(frame.orientation, vec![[1., 0., 0., 0.]; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn missing_position() -> Result<(), Error> {
+        let trj = GSDTrajectory::new(test_file("missing-position.gsd"))?;
+        assert!(trj.get_frame(0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn write_roundtrip() -> Result<(), Error> {
+        let mut filename = std::env::temp_dir();
+        filename.push(format!("gsd-write-roundtrip-{}.gsd", std::process::id()));
+
+        let mut trj = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let mut frames: Vec<GSDFrame> = trj.by_ref().skip(1).take(2).collect::<Result<_, _>>()?;
+        for (index, t) in frames[0].typeid.iter_mut().enumerate() {
+            *t = (index % 2) as u32;
+        }
+        frames[0].bonds = vec![[0, 1], [4, 7]];
+        {
+            let mut writer = GSDWriter::create(&filename)?;
+            for frame in frames.iter() {
+                writer.write_frame(frame)?;
+            }
+        }
+
+        let written = GSDTrajectory::new(&filename)?;
+        assert_eq!(written.nframes(), 2);
+        for (index, frame) in frames.iter().enumerate() {
+            let read = written.get_frame(index as u64)?;
+            assert_eq!(read.timestep, frame.timestep);
+            assert_eq!(read.simulation_cell, frame.simulation_cell);
+            assert_eq!(read.position, frame.position);
+            assert_eq!(read.orientation, frame.orientation);
+            assert_eq!(read.image, frame.image);
+            assert_eq!(read.angmom, frame.angmom);
+            assert_eq!(read.typeid, frame.typeid);
+            assert_eq!(read.body, frame.body);
+            assert_eq!(read.bonds, frame.bonds);
+            assert_eq!(read.dimensions, Some(2));
+        }
+        std::fs::remove_file(filename)?;
+        Ok(())
+    }
+
+    #[test]
+    fn decimate_trajectory() -> Result<(), Error> {
+        let mut filename = std::env::temp_dir();
+        filename.push(format!("gsd-decimate-{}.gsd", std::process::id()));
+
+        decimate(test_file("trajectory.gsd"), &filename, 2, None)?;
+        let original = GSDTrajectory::new(test_file("trajectory.gsd"))?;
+        let written = GSDTrajectory::new(&filename)?;
+        assert_eq!(written.nframes(), original.nframes() / 2);
+        for (index, frame) in written.enumerate() {
+            let frame = frame?;
+            let expected = original.get_frame(2 * index as u64)?;
+            assert_eq!(frame.timestep, expected.timestep);
+            assert_eq!(frame.position, expected.position);
+        }
+        std::fs::remove_file(&filename)?;
+
+        decimate(test_file("trajectory.gsd"), &filename, 3, Some(2))?;
+        assert_eq!(GSDTrajectory::new(&filename)?.nframes(), 2);
+        std::fs::remove_file(&filename)?;
+
+        assert!(decimate(test_file("trajectory.gsd"), &filename, 0, None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decimate_unreadable_frame() {
+        let mut filename = std::env::temp_dir();
+        filename.push(format!(
+            "gsd-decimate-unreadable-{}.gsd",
+            std::process::id()
+        ));
+        let result = decimate(test_file("missing-position.gsd"), &filename, 1, None);
+        std::fs::remove_file(&filename).unwrap_or(());
+        assert!(result.is_err());
+    }
 }
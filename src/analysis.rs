@@ -0,0 +1,762 @@
+//
+// analysis.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Run the complete analysis of a frame with the parameters from a configuration
+
+use crate::frame::Frame;
+use crate::knn::KNN;
+use crate::learning::{class_fractions, extract_features, Classes};
+use crate::metric::{compute_metrics, PerParticleMetric};
+use crate::order::{hexatic_order, orientational_order};
+use crate::voronoi::voronoi_area;
+use anyhow::{bail, Error};
+use itertools::izip;
+use serde::ser::SerializeTuple;
+use serde::{Deserialize, Serialize, Serializer};
+use std::ops::AddAssign;
+use std::time::{Duration, Instant};
+
+/// The parameters controlling the analysis of each frame
+///
+/// The default values match those used by the command line interface, with the builder methods
+/// modifying the value of a single parameter.
+///
+/// ```
+/// use trajedy::analysis::AnalysisConfig;
+///
+/// let config = AnalysisConfig::default().num_neighbours(4).compute_voronoi(true);
+/// assert_eq!(config.num_neighbours, 4);
+/// ```
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct AnalysisConfig {
+    /// The number of neighbours used to compute the order parameters
+    pub num_neighbours: usize,
+    /// Whether to compute the orientational and hexatic order, otherwise only classifying each
+    /// particle
+    pub compute_order: bool,
+    /// Whether to compute the area of the voronoi cell of each particle
+    pub compute_voronoi: bool,
+    /// Whether to compute the hexatic order of each particle
+    pub compute_hexatic: bool,
+    /// Particles within this fraction of the cell from the centre are labelled crystalline when
+    /// training the classifier
+    pub crystal_region: f32,
+    /// Particles beyond this fraction of the cell from the centre are labelled liquid when
+    /// training the classifier. Those between the crystal and liquid regions are ignored.
+    pub liquid_region: f32,
+    /// The number of nearest neighbours which vote on the classification of a particle
+    pub knn_k: usize,
+}
+
+impl Default for AnalysisConfig {
+    fn default() -> Self {
+        AnalysisConfig {
+            num_neighbours: 6,
+            compute_order: true,
+            compute_voronoi: false,
+            compute_hexatic: true,
+            crystal_region: 0.28,
+            liquid_region: 0.32,
+            knn_k: 5,
+        }
+    }
+}
+
+impl AnalysisConfig {
+    pub fn num_neighbours(mut self, num_neighbours: usize) -> Self {
+        self.num_neighbours = num_neighbours;
+        self
+    }
+
+    pub fn compute_order(mut self, compute_order: bool) -> Self {
+        self.compute_order = compute_order;
+        self
+    }
+
+    pub fn compute_voronoi(mut self, compute_voronoi: bool) -> Self {
+        self.compute_voronoi = compute_voronoi;
+        self
+    }
+
+    pub fn compute_hexatic(mut self, compute_hexatic: bool) -> Self {
+        self.compute_hexatic = compute_hexatic;
+        self
+    }
+
+    pub fn training_regions(mut self, crystal_region: f32, liquid_region: f32) -> Self {
+        self.crystal_region = crystal_region;
+        self.liquid_region = liquid_region;
+        self
+    }
+
+    pub fn knn_k(mut self, knn_k: usize) -> Self {
+        self.knn_k = knn_k;
+        self
+    }
+}
+
+/// A row of the output, containing the values for a single particle
+#[derive(Serialize, Deserialize)]
+pub struct Row {
+    pub molecule: usize,
+    pub timestep: usize,
+    pub orient_order: Option<f32>,
+    pub hexatic_order: Option<f32>,
+    pub class: Classes,
+    pub area: Option<f64>,
+}
+
+impl Row {
+    /// The names of the columns in the order they are written
+    pub const COLUMNS: [&'static str; 6] = [
+        "molecule",
+        "timestep",
+        "orient_order",
+        "hexatic_order",
+        "class",
+        "area",
+    ];
+}
+
+/// The formatting of the rows written to the output
+///
+/// By default every column of a [`Row`] is written with the full precision of each value,
+/// followed by a column for each of the metrics.
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RowFormat {
+    precision: Option<usize>,
+    columns: Option<Vec<String>>,
+    metrics: Vec<String>,
+}
+
+impl RowFormat {
+    /// Round the floating point values to this number of decimal places
+    pub fn precision(mut self, precision: usize) -> Self {
+        self.precision = Some(precision);
+        self
+    }
+
+    /// Write a column for each of these metrics after the columns of the [`Row`]
+    ///
+    /// The metrics need to be set before selecting the columns, so they can be chosen.
+    ///
+    pub fn metrics(mut self, metrics: Vec<String>) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Only write these columns in the given order, named from [`Row::COLUMNS`] or the metrics
+    pub fn columns(mut self, columns: Vec<String>) -> Result<Self, Error> {
+        let available: Vec<&str> = Row::COLUMNS
+            .iter()
+            .copied()
+            .chain(self.metrics.iter().map(String::as_str))
+            .collect();
+        if let Some(column) = columns.iter().find(|c| !available.contains(&c.as_str())) {
+            bail!(
+                "The column '{}' doesn't exist, the available columns are {:?}",
+                column,
+                available
+            );
+        }
+        self.columns = Some(columns);
+        Ok(self)
+    }
+
+    /// The names of the columns which are written, in the order they are written
+    pub fn header(&self) -> Vec<&str> {
+        match &self.columns {
+            Some(columns) => columns.iter().map(String::as_str).collect(),
+            None => Row::COLUMNS
+                .iter()
+                .copied()
+                .chain(self.metrics.iter().map(String::as_str))
+                .collect(),
+        }
+    }
+
+    /// A view of the row which is serialized using this format
+    ///
+    /// This doesn't include a header, which is written separately using [`RowFormat::header`].
+    ///
+    pub fn format<'a>(&'a self, row: &'a Row) -> FormattedRow<'a> {
+        self.format_with_metrics(row, &[])
+    }
+
+    /// A view of the row along with the value of each metric for the same particle
+    pub fn format_with_metrics<'a>(&'a self, row: &'a Row, metrics: &'a [f32]) -> FormattedRow<'a> {
+        FormattedRow {
+            row,
+            metrics,
+            format: self,
+        }
+    }
+
+    fn round(&self, value: f64) -> f64 {
+        match self.precision {
+            Some(precision) => {
+                let scale = 10_f64.powi(precision as i32);
+                (value * scale).round() / scale
+            }
+            None => value,
+        }
+    }
+
+    fn round_f32(&self, value: f32) -> f32 {
+        match self.precision {
+            Some(_) => self.round(value as f64) as f32,
+            None => value,
+        }
+    }
+}
+
+/// A [`Row`] and the values of the metrics, along with the [`RowFormat`] used to serialize them
+pub struct FormattedRow<'a> {
+    row: &'a Row,
+    metrics: &'a [f32],
+    format: &'a RowFormat,
+}
+
+impl Serialize for FormattedRow<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let (row, format) = (self.row, self.format);
+        let columns = format.header();
+        let mut state = serializer.serialize_tuple(columns.len())?;
+        for column in columns {
+            match column {
+                "molecule" => state.serialize_element(&row.molecule)?,
+                "timestep" => state.serialize_element(&row.timestep)?,
+                "orient_order" => {
+                    state.serialize_element(&row.orient_order.map(|v| format.round_f32(v)))?
+                }
+                "hexatic_order" => {
+                    state.serialize_element(&row.hexatic_order.map(|v| format.round_f32(v)))?
+                }
+                "class" => state.serialize_element(&row.class)?,
+                "area" => state.serialize_element(&row.area.map(|v| format.round(v)))?,
+                metric => {
+                    let index = format
+                        .metrics
+                        .iter()
+                        .position(|m| m == metric)
+                        .expect("All the columns are checked when creating the format");
+                    let value = self.metrics.get(index).map(|&v| format.round_f32(v));
+                    state.serialize_element(&value)?
+                }
+            }
+        }
+        state.end()
+    }
+}
+
+/// A row of the features used for classification, containing the values for a single particle
+#[derive(Serialize)]
+pub struct FeatureRow {
+    pub molecule: usize,
+    pub timestep: usize,
+    pub feature_0: f32,
+    pub feature_1: f32,
+    pub feature_2: f32,
+    pub feature_3: f32,
+    pub feature_4: f32,
+    pub feature_5: f32,
+}
+
+/// Create a row for each particle from the features of a frame
+pub fn feature_rows(timestep: usize, features: &[[f32; 6]]) -> Vec<FeatureRow> {
+    features
+        .iter()
+        .enumerate()
+        .map(|(molecule, f)| FeatureRow {
+            molecule,
+            timestep,
+            feature_0: f[0],
+            feature_1: f[1],
+            feature_2: f[2],
+            feature_3: f[3],
+            feature_4: f[4],
+            feature_5: f[5],
+        })
+        .collect()
+}
+
+/// The values computed for every particle within a single frame
+#[derive(Clone, Debug, PartialEq)]
+pub struct CalcResult {
+    pub timestep: usize,
+    pub orient_order: Option<Vec<f32>>,
+    pub hexatic_order: Option<Vec<f32>>,
+    pub class: Vec<Classes>,
+    pub area: Option<Vec<f64>>,
+    /// The values of any additional metrics, along with the name of each metric
+    pub metrics: Vec<(String, Vec<f32>)>,
+}
+
+impl CalcResult {
+    /// The value of each metric for a single particle
+    pub fn metric_values(&self, index: usize) -> Vec<f32> {
+        self.metrics
+            .iter()
+            .map(|(_, values)| values[index])
+            .collect()
+    }
+}
+
+/// The average values over all the particles within a single frame
+///
+/// This is serialized without a header, with the columns given by [`Summary::header`].
+///
+pub struct Summary {
+    pub timestep: usize,
+    pub orient_order: Option<f32>,
+    pub hexatic_order: Option<f32>,
+    pub liquid: f32,
+    pub p2: f32,
+    pub p2gg: f32,
+    pub pg: f32,
+    /// The mean value of each of the metrics, along with the name of the metric
+    pub metrics: Vec<(String, f32)>,
+}
+
+impl Summary {
+    /// The names of the columns which are always written, in the order they are written
+    pub const COLUMNS: [&'static str; 7] = [
+        "timestep",
+        "orient_order",
+        "hexatic_order",
+        "liquid",
+        "p2",
+        "p2gg",
+        "pg",
+    ];
+
+    /// The names of the columns, followed by a column for each of the `metrics`
+    pub fn header(metrics: &[String]) -> Vec<&str> {
+        Summary::COLUMNS
+            .iter()
+            .copied()
+            .chain(metrics.iter().map(String::as_str))
+            .collect()
+    }
+}
+
+impl Serialize for Summary {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_tuple(Summary::COLUMNS.len() + self.metrics.len())?;
+        state.serialize_element(&self.timestep)?;
+        state.serialize_element(&self.orient_order)?;
+        state.serialize_element(&self.hexatic_order)?;
+        state.serialize_element(&self.liquid)?;
+        state.serialize_element(&self.p2)?;
+        state.serialize_element(&self.p2gg)?;
+        state.serialize_element(&self.pg)?;
+        for (_, value) in self.metrics.iter() {
+            state.serialize_element(value)?;
+        }
+        state.end()
+    }
+}
+
+fn mean(values: &[f32]) -> f32 {
+    values.iter().sum::<f32>() / values.len() as f32
+}
+
+impl From<&CalcResult> for Summary {
+    fn from(result: &CalcResult) -> Summary {
+        let fractions = class_fractions(&result.class);
+        let fraction = |class: Classes| fractions[&class];
+        Summary {
+            timestep: result.timestep,
+            orient_order: result.orient_order.as_deref().map(mean),
+            hexatic_order: result.hexatic_order.as_deref().map(mean),
+            liquid: fraction(Classes::Liquid),
+            p2: fraction(Classes::P2),
+            p2gg: fraction(Classes::P2GG),
+            pg: fraction(Classes::PG),
+            metrics: result
+                .metrics
+                .iter()
+                .map(|(name, values)| (name.clone(), mean(values)))
+                .collect(),
+        }
+    }
+}
+
+/// Convert an optional collection of values into an iterator which never finishes
+fn unwrap_optional<T: 'static>(values: Option<Vec<T>>) -> Box<dyn Iterator<Item = Option<T>>> {
+    match values {
+        Some(v) => Box::new(v.into_iter().map(Some)),
+        None => Box::new((0..).map(|_| None)),
+    }
+}
+
+impl From<CalcResult> for Vec<Row> {
+    fn from(result: CalcResult) -> Vec<Row> {
+        let timestep = result.timestep;
+        izip!(
+            0..,
+            unwrap_optional(result.orient_order),
+            unwrap_optional(result.hexatic_order),
+            result.class.into_iter(),
+            unwrap_optional(result.area),
+        )
+        .map(|(molecule, orient_order, hexatic_order, class, area)| Row {
+            molecule,
+            timestep,
+            orient_order,
+            hexatic_order,
+            class,
+            area,
+        })
+        .collect()
+    }
+}
+
+/// The time taken by each stage of the analysis of a frame
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Timing {
+    /// Converting the frame read from the file, which includes building the neighbour tree
+    pub create_frame: Duration,
+    /// Computing the orientational and hexatic order
+    pub order: Duration,
+    pub features: Duration,
+    pub predict: Duration,
+    pub voronoi: Duration,
+}
+
+impl Timing {
+    /// The names of each stage along with the time it took
+    pub fn stages(&self) -> [(&'static str, Duration); 5] {
+        [
+            ("create_frame", self.create_frame),
+            ("order", self.order),
+            ("features", self.features),
+            ("predict", self.predict),
+            ("voronoi", self.voronoi),
+        ]
+    }
+
+    pub fn total(&self) -> Duration {
+        self.stages().iter().map(|(_, duration)| *duration).sum()
+    }
+}
+
+impl AddAssign for Timing {
+    fn add_assign(&mut self, other: Self) {
+        self.create_frame += other.create_frame;
+        self.order += other.order;
+        self.features += other.features;
+        self.predict += other.predict;
+        self.voronoi += other.voronoi;
+    }
+}
+
+/// Compute all the quantities specified in the configuration for a single frame
+///
+/// This returns an error for a frame containing non-finite positions, or for a model trained
+/// with different features, rather than computing meaningless values. Without a trained model
+/// every particle is classified as liquid.
+///
+pub fn analyse_frame(
+    frame: &Frame,
+    knn: &KNN<[f32; 6], Classes>,
+    config: &AnalysisConfig,
+) -> Result<CalcResult, Error> {
+    analyse_frame_timed(frame, knn, config).map(|(result, _)| result)
+}
+
+/// Compute the quantities for a single frame, recording the time taken by each stage
+///
+/// This is the same as [`analyse_frame`], with the time to create the frame left as zero since
+/// this happens before the analysis.
+///
+pub fn analyse_frame_timed(
+    frame: &Frame,
+    knn: &KNN<[f32; 6], Classes>,
+    config: &AnalysisConfig,
+) -> Result<(CalcResult, Timing), Error> {
+    frame.validate()?;
+    let mut timing = Timing::default();
+
+    let start = Instant::now();
+    let orient_order = if config.compute_order {
+        Some(orientational_order(frame, config.num_neighbours))
+    } else {
+        None
+    };
+    let hexatic_order = if config.compute_order && config.compute_hexatic {
+        Some(hexatic_order(frame, config.num_neighbours))
+    } else {
+        None
+    };
+    timing.order = start.elapsed();
+
+    let start = Instant::now();
+    let features = extract_features(frame);
+    timing.features = start.elapsed();
+
+    let start = Instant::now();
+    // Without a trained model every particle is classified as liquid
+    let class = if knn.is_trained() {
+        knn.predict(&features)?
+    } else {
+        vec![Classes::Liquid; frame.len()]
+    };
+    timing.predict = start.elapsed();
+
+    let start = Instant::now();
+    let area = if config.compute_voronoi {
+        Some(voronoi_area(frame)?)
+    } else {
+        None
+    };
+    timing.voronoi = start.elapsed();
+
+    let result = CalcResult {
+        timestep: frame.timestep as usize,
+        orient_order,
+        hexatic_order,
+        class,
+        area,
+        metrics: Vec::new(),
+    };
+    Ok((result, timing))
+}
+
+/// Compute the quantities for a single frame along with each of the additional metrics
+///
+/// The time taken to compute the metrics is included in the order stage of the timing.
+///
+pub fn analyse_frame_metrics(
+    frame: &Frame,
+    knn: &KNN<[f32; 6], Classes>,
+    config: &AnalysisConfig,
+    metrics: &[Box<dyn PerParticleMetric>],
+) -> Result<(CalcResult, Timing), Error> {
+    let (mut result, mut timing) = analyse_frame_timed(frame, knn, config)?;
+    let start = Instant::now();
+    result.metrics = compute_metrics(frame, metrics);
+    timing.order += start.elapsed();
+    Ok((result, timing))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn default_config() {
+        let config = AnalysisConfig::default();
+        assert_eq!(config.num_neighbours, 6);
+        assert!(config.compute_order);
+        assert!(!config.compute_voronoi);
+        assert!(config.compute_hexatic);
+        assert_eq!(config.knn_k, 5);
+    }
+
+    #[test]
+    fn builder_config() {
+        let config = AnalysisConfig::default()
+            .num_neighbours(4)
+            .compute_voronoi(true)
+            .compute_hexatic(false)
+            .training_regions(0.2, 0.3)
+            .knn_k(3);
+        assert_eq!(config.num_neighbours, 4);
+        assert!(config.compute_voronoi);
+        assert!(!config.compute_hexatic);
+        assert_eq!(config.crystal_region, 0.2);
+        assert_eq!(config.liquid_region, 0.3);
+        assert_eq!(config.knn_k, 3);
+    }
+
+    #[test]
+    fn result_rows() {
+        let result = CalcResult {
+            timestep: 10,
+            orient_order: Some(vec![0.1, 0.2]),
+            hexatic_order: None,
+            class: vec![Classes::Liquid, Classes::P2],
+            area: Some(vec![1., 2.]),
+            metrics: Vec::new(),
+        };
+        let rows: Vec<Row> = result.into();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].molecule, 1);
+        assert_eq!(rows[1].timestep, 10);
+        assert_eq!(rows[1].hexatic_order, None);
+        assert_eq!(rows[1].class, Classes::P2);
+        assert_eq!(rows[1].area, Some(2.));
+    }
+
+    #[test]
+    fn timing_total() {
+        let mut timing = Timing {
+            order: Duration::from_millis(2),
+            predict: Duration::from_millis(3),
+            ..Timing::default()
+        };
+        assert_eq!(timing.total(), Duration::from_millis(5));
+        timing += timing;
+        assert_eq!(timing.order, Duration::from_millis(4));
+        assert_eq!(timing.total(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn result_summary() {
+        let result = CalcResult {
+            timestep: 10,
+            orient_order: Some(vec![0.1, 0.3]),
+            hexatic_order: Some(vec![0.5, 1.]),
+            class: vec![Classes::Liquid, Classes::P2],
+            area: None,
+            metrics: Vec::new(),
+        };
+        let summary = Summary::from(&result);
+        assert_eq!(summary.timestep, 10);
+        assert_abs_diff_eq!(summary.orient_order.unwrap(), 0.2);
+        assert_eq!(summary.hexatic_order, Some(0.75));
+        assert_eq!(summary.liquid, 0.5);
+        assert_eq!(summary.p2, 0.5);
+        assert_eq!(summary.pg, 0.);
+        assert!(summary.metrics.is_empty());
+    }
+
+    #[test]
+    fn summary_metrics() -> Result<(), Error> {
+        let result = CalcResult {
+            timestep: 10,
+            orient_order: None,
+            hexatic_order: None,
+            class: vec![Classes::Liquid, Classes::P2],
+            area: None,
+            metrics: vec![("q6".to_string(), vec![0.25, 0.75])],
+        };
+        let summary = Summary::from(&result);
+        assert_eq!(summary.metrics, vec![("q6".to_string(), 0.5)]);
+
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.write_record(Summary::header(&["q6".to_string()]))?;
+        wtr.serialize(summary)?;
+        assert_eq!(
+            String::from_utf8(wtr.into_inner()?)?,
+            "timestep,orient_order,hexatic_order,liquid,p2,p2gg,pg,q6\n10,,,0.5,0.5,0.0,0.0,0.5\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn formatted_rows() -> Result<(), Error> {
+        let row = Row {
+            molecule: 1,
+            timestep: 10,
+            orient_order: Some(0.123456),
+            hexatic_order: Some(0.98765),
+            class: Classes::P2,
+            area: Some(1.00049),
+        };
+        let write = |format: &RowFormat| -> Result<String, Error> {
+            let mut wtr = csv::Writer::from_writer(vec![]);
+            wtr.write_record(format.header())?;
+            wtr.serialize(format.format(&row))?;
+            Ok(String::from_utf8(wtr.into_inner()?)?)
+        };
+
+        assert_eq!(
+            write(&RowFormat::default())?,
+            "molecule,timestep,orient_order,hexatic_order,class,area\n\
+             1,10,0.123456,0.98765,P2,1.00049\n"
+        );
+        assert_eq!(
+            write(&RowFormat::default().precision(3))?,
+            "molecule,timestep,orient_order,hexatic_order,class,area\n\
+             1,10,0.123,0.988,P2,1.0\n"
+        );
+        let format = RowFormat::default()
+            .precision(2)
+            .columns(vec!["orient_order".into(), "molecule".into()])?;
+        assert_eq!(write(&format)?, "orient_order,molecule\n0.12,1\n");
+        assert!(RowFormat::default().columns(vec!["volume".into()]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn metric_columns() -> Result<(), Error> {
+        use crate::metric::SteinhardtOrder;
+        use gsd::GSDFrame;
+
+        let position: Vec<[f32; 3]> = (0..4)
+            .flat_map(|y| (0..4).map(move |x| [x as f32, y as f32, 0.]))
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [4., 4., 1., 0., 0., 0.]).into();
+        let metrics: Vec<Box<dyn PerParticleMetric>> = vec![
+            Box::new(SteinhardtOrder::new(4, 4)),
+            Box::new(SteinhardtOrder::new(6, 4)),
+        ];
+        let config = AnalysisConfig::default().compute_hexatic(false);
+        let (result, _) = analyse_frame_metrics(&frame, &KNN::default(), &config, &metrics)?;
+
+        let format = RowFormat::default().metrics(
+            result
+                .metrics
+                .iter()
+                .map(|(name, _)| name.clone())
+                .collect(),
+        );
+        let values = result.metric_values(0);
+        let rows: Vec<Row> = result.into();
+        let mut wtr = csv::Writer::from_writer(vec![]);
+        wtr.write_record(format.header())?;
+        wtr.serialize(format.format_with_metrics(&rows[0], &values))?;
+        let contents = String::from_utf8(wtr.into_inner()?)?;
+
+        let mut lines = contents.lines();
+        assert_eq!(
+            lines.next(),
+            Some("molecule,timestep,orient_order,hexatic_order,class,area,q4,q6")
+        );
+        assert_eq!(lines.next().map(|l| l.split(',').count()), Some(8));
+
+        let format = format.columns(vec!["q6".into(), "molecule".into()])?;
+        assert_eq!(format.header(), ["q6", "molecule"]);
+        Ok(())
+    }
+
+    #[test]
+    fn classify_only() -> Result<(), Error> {
+        use gsd::GSDTrajectory;
+
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let frame: Frame = GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        let config = AnalysisConfig::default().compute_order(false);
+        let result = analyse_frame(&frame, &KNN::default(), &config)?;
+        assert_eq!(result.orient_order, None);
+        assert_eq!(result.hexatic_order, None);
+        assert_eq!(result.class.len(), frame.len());
+        Ok(())
+    }
+
+    #[test]
+    fn model_dimension_mismatch() -> Result<(), Error> {
+        use gsd::GSDTrajectory;
+
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let frame: Frame = GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        let mut knn: KNN<[f32; 6], Classes> = KNN::default();
+        knn.fit(&[[0.; 6]; 10], &[Classes::P2; 10])?;
+        // A model file which records training with a different number of features
+        let contents = serde_json::to_string(&knn)?.replace("\"dimensions\":6", "\"dimensions\":4");
+        let knn: KNN<[f32; 6], Classes> = serde_json::from_str(&contents)?;
+        let config = AnalysisConfig::default();
+        assert!(analyse_frame(&frame, &knn, &config).is_err());
+        Ok(())
+    }
+}
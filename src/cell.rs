@@ -0,0 +1,325 @@
+//
+// cell.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! A typed representation of the periodic simulation cell
+
+use nalgebra::Matrix3;
+use serde::{Deserialize, Serialize};
+
+/// The periodic simulation cell of a configuration
+///
+/// This follows the hoomd convention for a triclinic cell, which is described by the length of
+/// each side along with the tilt factors xy, xz, and yz. When represented as an array, the values
+/// are ordered `[lx, ly, lz, xy, xz, yz]`.
+///
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SimulationCell {
+    pub lx: f32,
+    pub ly: f32,
+    pub lz: f32,
+    pub xy: f32,
+    pub xz: f32,
+    pub yz: f32,
+}
+
+impl SimulationCell {
+    /// The volume of the cell, which for the hoomd convention is independent of the tilt factors
+    pub fn volume(&self) -> f32 {
+        self.lx * self.ly * self.lz
+    }
+
+    pub fn lengths(&self) -> [f32; 3] {
+        [self.lx, self.ly, self.lz]
+    }
+
+    pub fn tilts(&self) -> [f32; 3] {
+        [self.xy, self.xz, self.yz]
+    }
+
+    /// The perpendicular distance between each pair of opposite faces of the cell
+    ///
+    /// This is the same as the lengths for an orthorhombic cell, with the tilt factors reducing
+    /// the distance between the faces which are not perpendicular to the lattice vectors.
+    ///
+    pub fn heights(&self) -> [f32; 3] {
+        let (xy, xz, yz) = (self.xy, self.xz, self.yz);
+        [
+            self.lx / (1. + xy * xy + (xz - yz * xy).powi(2)).sqrt(),
+            self.ly / (1. + yz * yz).sqrt(),
+            self.lz,
+        ]
+    }
+
+    /// The matrix with the lattice vectors of the cell as the columns
+    pub fn to_matrix(&self) -> Matrix3<f32> {
+        Matrix3::new(
+            self.lx,
+            self.xy * self.ly,
+            self.xz * self.lz,
+            0.,
+            self.ly,
+            self.yz * self.lz,
+            0.,
+            0.,
+            self.lz,
+        )
+    }
+
+    /// Create a cell from the upper triangular matrix of lattice vectors
+    ///
+    /// This is the inverse of [`SimulationCell::to_matrix`], with any values in the lower triangle
+    /// of the matrix ignored.
+    ///
+    pub fn from_matrix(matrix: &Matrix3<f32>) -> Self {
+        let (lx, ly, lz) = (matrix[(0, 0)], matrix[(1, 1)], matrix[(2, 2)]);
+        SimulationCell {
+            lx,
+            ly,
+            lz,
+            xy: matrix[(0, 1)] / ly,
+            xz: matrix[(0, 2)] / lz,
+            yz: matrix[(1, 2)] / lz,
+        }
+    }
+
+    /// Create a cell from the bounds and tilt factors of a lammps triclinic box
+    ///
+    /// Lammps describes the box using the bounds `lo` and `hi` of each dimension, along with the
+    /// tilt factors `[xy, xz, yz]` which are lengths, rather than the fractions used by hoomd.
+    /// The lammps box has the origin at the corner `lo`, while the hoomd cell is centred on the
+    /// origin, so the lammps positions have to be shifted by the caller, subtracting the centre
+    /// of the lammps box given by [`SimulationCell::lammps_centre`].
+    ///
+    pub fn from_lammps_bounds(lo: [f32; 3], hi: [f32; 3], tilt: [f32; 3]) -> Self {
+        let (lx, ly, lz) = (hi[0] - lo[0], hi[1] - lo[1], hi[2] - lo[2]);
+        SimulationCell {
+            lx,
+            ly,
+            lz,
+            xy: tilt[0] / ly,
+            xz: tilt[1] / lz,
+            yz: tilt[2] / lz,
+        }
+    }
+
+    /// The centre of a lammps triclinic box, with the same arguments as [`Self::from_lammps_bounds`]
+    ///
+    /// This is `lo + (a + b + c) / 2` for the lattice vectors `a`, `b` and `c` of the box, so
+    /// with a tilt the centre is not halfway between `lo` and `hi`. Subtracting the centre from a
+    /// lammps position gives the position within the hoomd cell.
+    ///
+    pub fn lammps_centre(lo: [f32; 3], hi: [f32; 3], tilt: [f32; 3]) -> [f32; 3] {
+        let [xy, xz, yz] = tilt;
+        [
+            lo[0] + (hi[0] - lo[0] + xy + xz) / 2.,
+            lo[1] + (hi[1] - lo[1] + yz) / 2.,
+            lo[2] + (hi[2] - lo[2]) / 2.,
+        ]
+    }
+
+    /// Convert a cartesian point into fractional coordinates
+    ///
+    /// The fractional coordinates are in the range [0, 1) for points within the cell.
+    ///
+    #[inline]
+    pub fn make_fractional(&self, point: &[f32; 3]) -> [f32; 3] {
+        let mut p = [0.; 3];
+
+        p[0] = point[0] + 0.5 * self.lx;
+        p[1] = point[1] + 0.5 * self.ly;
+        p[2] = point[2] + 0.5 * self.lz;
+
+        p[0] -= (self.xz - self.yz * self.xy) * point[2] + self.xy * point[1];
+        p[1] -= self.yz * point[2];
+
+        p[0] /= self.lx;
+        p[1] /= self.ly;
+        p[2] /= self.lz;
+
+        p
+    }
+
+    /// Convert a point in fractional coordinates into cartesian coordinates
+    #[inline]
+    pub fn make_cartesian(&self, point: &[f32; 3]) -> [f32; 3] {
+        let mut p = [0.; 3];
+
+        p[0] = (point[0] - 0.5) * self.lx;
+        p[1] = (point[1] - 0.5) * self.ly;
+        p[2] = (point[2] - 0.5) * self.lz;
+
+        p[0] += self.xy * p[1] + self.xz * p[2];
+        p[1] += self.yz * p[2];
+
+        p
+    }
+
+    /// Wrap a point into the periodic image within the cell
+    #[inline]
+    pub fn min_image(&self, point: &[f32; 3]) -> [f32; 3] {
+        self.min_image_with_shift(point).0
+    }
+
+    /// Wrap a point into the cell, along with the number of lattice vectors it was moved by
+    ///
+    /// The original point is the wrapped point plus the lattice vectors multiplied by the shift,
+    /// which is the same as the image of the particle in the hoomd convention.
+    ///
+    #[inline]
+    pub fn min_image_with_shift(&self, point: &[f32; 3]) -> ([f32; 3], [i32; 3]) {
+        let mut fractional = self.make_fractional(point);
+        let mut shift = [0; 3];
+        for (f, s) in fractional.iter_mut().zip(shift.iter_mut()) {
+            let image = f.floor();
+            *f -= image;
+            *s = image as i32;
+        }
+        (self.make_cartesian(&fractional), shift)
+    }
+}
+
+impl From<[f32; 6]> for SimulationCell {
+    fn from(cell: [f32; 6]) -> Self {
+        SimulationCell {
+            lx: cell[0],
+            ly: cell[1],
+            lz: cell[2],
+            xy: cell[3],
+            xz: cell[4],
+            yz: cell[5],
+        }
+    }
+}
+
+impl From<SimulationCell> for [f32; 6] {
+    fn from(cell: SimulationCell) -> Self {
+        [cell.lx, cell.ly, cell.lz, cell.xy, cell.xz, cell.yz]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use nalgebra::Vector3;
+
+    #[test]
+    fn array_roundtrip() {
+        let array = [1., 2., 3., 0.1, 0.2, 0.3];
+        let cell = SimulationCell::from(array);
+        assert_eq!(cell.lengths(), [1., 2., 3.]);
+        assert_eq!(cell.tilts(), [0.1, 0.2, 0.3]);
+        assert_eq!(<[f32; 6]>::from(cell), array);
+    }
+
+    #[test]
+    fn volume_tilted() {
+        let cell = SimulationCell::from([2., 3., 4., 0.5, -0.2, 0.8]);
+        assert_abs_diff_eq!(cell.volume(), 24.);
+        assert_abs_diff_eq!(cell.to_matrix().determinant(), 24., epsilon = 1e-5);
+    }
+
+    #[test]
+    fn heights_tilted() {
+        let cell = SimulationCell::from([2., 3., 4., 0.5, -0.2, 0.8]);
+        let matrix = cell.to_matrix();
+        let (a, b, c) = (matrix.column(0), matrix.column(1), matrix.column(2));
+        let heights = cell.heights();
+        assert_abs_diff_eq!(
+            heights[0],
+            cell.volume() / b.cross(&c).norm(),
+            epsilon = 1e-5
+        );
+        assert_abs_diff_eq!(
+            heights[1],
+            cell.volume() / c.cross(&a).norm(),
+            epsilon = 1e-5
+        );
+        assert_abs_diff_eq!(
+            heights[2],
+            cell.volume() / a.cross(&b).norm(),
+            epsilon = 1e-5
+        );
+    }
+
+    #[test]
+    fn matrix_roundtrip() {
+        let cell = SimulationCell::from([2., 3., 4., 0.5, -0.2, 0.8]);
+        assert_eq!(SimulationCell::from_matrix(&cell.to_matrix()), cell);
+    }
+
+    #[test]
+    fn matrix_cartesian() {
+        let cell = SimulationCell::from([2., 3., 4., 0.5, -0.2, 0.8]);
+        let fractional = [0.2, 0.7, 0.9];
+        let expected = cell.to_matrix() * Vector3::new(-0.3, 0.2, 0.4);
+        let point = cell.make_cartesian(&fractional);
+        for i in 0..3 {
+            assert_abs_diff_eq!(point[i], expected[i], epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn lammps_tilted() {
+        let (lo, hi, tilt) = ([-1., 0., 2.], [3., 2., 5.], [0.5, -0.6, 0.9]);
+        let cell = SimulationCell::from_lammps_bounds(lo, hi, tilt);
+        assert_eq!(cell.lengths(), [4., 2., 3.]);
+        assert_abs_diff_eq!(cell.xy, 0.25);
+        assert_abs_diff_eq!(cell.xz, -0.2);
+        assert_abs_diff_eq!(cell.yz, 0.3);
+
+        // The lattice vectors of the lammps box
+        let a = Vector3::new(4., 0., 0.);
+        let b = Vector3::new(0.5, 2., 0.);
+        let c = Vector3::new(-0.6, 0.9, 3.);
+        let origin = Vector3::from(cell.make_cartesian(&[0., 0., 0.]));
+        for fractional in [[0.2, 0.7, 0.9], [1., 0., 0.], [0.5, 0.5, 0.5], [0., 1., 1.]].iter() {
+            let point = cell.make_cartesian(fractional);
+            let expected = origin + a * fractional[0] + b * fractional[1] + c * fractional[2];
+            for i in 0..3 {
+                assert_abs_diff_eq!(point[i], expected[i], epsilon = 1e-5);
+            }
+            let roundtrip = cell.make_fractional(&point);
+            for i in 0..3 {
+                assert_abs_diff_eq!(roundtrip[i], fractional[i], epsilon = 1e-5);
+            }
+        }
+        // The centre of the hoomd cell is the centre of the lammps box
+        let centre = Vector3::from(lo) + (a + b + c) / 2.;
+        let corner = Vector3::from(lo) - centre;
+        for i in 0..3 {
+            assert_abs_diff_eq!(origin[i], corner[i], epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn lammps_positions_tilted() {
+        let (lo, hi, tilt) = ([-1., 0., 2.], [3., 2., 5.], [0.5, -0.6, 0.9]);
+        let cell = SimulationCell::from_lammps_bounds(lo, hi, tilt);
+        let centre = Vector3::from(SimulationCell::lammps_centre(lo, hi, tilt));
+
+        let (a, b, c) = (
+            Vector3::new(4., 0., 0.),
+            Vector3::new(0.5, 2., 0.),
+            Vector3::new(-0.6, 0.9, 3.),
+        );
+        for fractional in [
+            [0.2, 0.7, 0.9],
+            [0., 0., 0.],
+            [0.5, 0.5, 0.5],
+            [0.9, 0.1, 0.3],
+        ]
+        .iter()
+        {
+            let lammps =
+                Vector3::from(lo) + a * fractional[0] + b * fractional[1] + c * fractional[2];
+            let position = cell.make_fractional(&(lammps - centre).into());
+            for i in 0..3 {
+                assert_abs_diff_eq!(position[i], fractional[i], epsilon = 1e-5);
+            }
+        }
+    }
+}
@@ -4,45 +4,32 @@
 // Distributed under terms of the MIT license.
 //
 
-#[inline]
-fn make_fractional(cell: &[f32; 6], point: &[f32; 3]) -> [f32; 3] {
-    let mut p = [0.; 3];
-
-    p[0] = point[0] + 0.5 * cell[0];
-    p[1] = point[1] + 0.5 * cell[1];
-    p[2] = point[2] + 0.5 * cell[2];
-
-    p[0] -= (cell[4] - cell[5] * cell[3]) * point[2] + cell[3] * point[1];
-    p[1] -= cell[5] * point[2];
+//! Periodic distance calculations using the array representation of the simulation cell
+//!
+//! These are thin wrappers around the methods of [`SimulationCell`], the array being in the form
+//! `[lx, ly, lz, xy, xz, yz]`.
 
-    p[0] /= cell[0];
-    p[1] /= cell[1];
-    p[2] /= cell[2];
+use crate::cell::SimulationCell;
 
-    p
+#[inline]
+pub fn make_fractional(cell: &[f32; 6], point: &[f32; 3]) -> [f32; 3] {
+    SimulationCell::from(*cell).make_fractional(point)
 }
 
 #[inline]
 pub(crate) fn make_cartesian(cell: &[f32; 6], point: &[f32; 3]) -> [f32; 3] {
-    let mut p = [0.; 3];
-
-    p[0] = (point[0] - 0.5) * cell[0];
-    p[1] = (point[1] - 0.5) * cell[1];
-    p[2] = (point[2] - 0.5) * cell[2];
-
-    p[0] += cell[3] * p[1] + cell[4] * p[2];
-    p[1] += cell[5] * p[2];
-
-    p
+    SimulationCell::from(*cell).make_cartesian(point)
 }
 
 #[inline]
 pub fn min_image(cell: &[f32; 6], point: &[f32; 3]) -> [f32; 3] {
-    let mut fractional = make_fractional(cell, point);
-    fractional[0] -= fractional[0].floor();
-    fractional[1] -= fractional[1].floor();
-    fractional[2] -= fractional[2].floor();
-    make_cartesian(cell, &fractional)
+    SimulationCell::from(*cell).min_image(point)
+}
+
+/// Wrap a point into the cell, returning the wrapped point along with the image shift applied
+#[inline]
+pub fn min_image_with_shift(cell: &[f32; 6], point: &[f32; 3]) -> ([f32; 3], [i32; 3]) {
+    SimulationCell::from(*cell).min_image_with_shift(point)
 }
 
 #[cfg(test)]
@@ -139,6 +126,45 @@ mod tests {
         }
     }
 
+    proptest! {
+        #[test]
+        fn min_image_shift_restores(
+            x in -5_f32..5_f32,
+            y in -5_f32..5_f32,
+            z in -5_f32..5_f32,
+            xy in -1_f32..1_f32,
+            xz in -1_f32..1_f32,
+            yz in -1_f32..1_f32
+        ) {
+            let cell = [2., 3., 1.5, xy, xz, yz];
+            let point = [x, y, z];
+            let (wrapped, shift) = min_image_with_shift(&cell, &point);
+            assert_eq!(wrapped, min_image(&cell, &point));
+            // Converting the shift to cartesian coordinates, including the offset of the origin
+            let offset = make_cartesian(&cell, &[0., 0., 0.]);
+            let shifted = make_cartesian(
+                &cell,
+                &[shift[0] as f32, shift[1] as f32, shift[2] as f32],
+            );
+            for i in 0..3 {
+                assert_abs_diff_eq!(shifted[i] - offset[i] + wrapped[i], point[i], epsilon = 1e-4);
+            }
+        }
+    }
+
+    #[test]
+    fn min_image_shift_integer() {
+        let cell = [2., 2., 2., 0., 0., 0.];
+        assert_eq!(
+            min_image_with_shift(&cell, &[0.5, 0., 0.]),
+            ([0.5, 0., 0.], [0, 0, 0])
+        );
+        assert_eq!(
+            min_image_with_shift(&cell, &[2.5, -2.5, 4.5]),
+            ([0.5, -0.5, 0.5], [1, -1, 2])
+        );
+    }
+
     #[test]
     fn to_cartesian_xy() {
         let cell = [1., 1., 1., 0.5, 0., 0.];
@@ -329,4 +355,51 @@ mod tests {
             assert!(point_frac[0] > -1.*std::f32::EPSILON);
         }
     }
+
+    proptest! {
+        #[test]
+        fn fractional_matrix_inverse(
+            x in -2_f32..2_f32,
+            y in -2_f32..2_f32,
+            z in -2_f32..2_f32,
+            xy in -1_f32..1_f32,
+            xz in -1_f32..1_f32,
+            yz in -1_f32..1_f32
+        ) {
+            // The fractional coordinates are the inverse of the lattice matrix applied to the
+            // point, checking the coupling of all three axes together.
+            let cell = [2., 3., 4., xy, xz, yz];
+            let lattice = SimulationCell::from(cell).to_matrix();
+            let expected = lattice.try_inverse().unwrap() * nalgebra::Vector3::new(x, y, z);
+
+            let fractional = make_fractional(&cell, &[x, y, z]);
+            for i in 0..3 {
+                assert_abs_diff_eq!(fractional[i], expected[i] + 0.5, epsilon = 1e-5);
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn fractional_z_displacement(
+            x in -1_f32..1_f32,
+            y in -1_f32..1_f32,
+            z in -1_f32..1_f32,
+            dz in -2_f32..2_f32,
+            xy in -1_f32..1_f32,
+            xz in -1_f32..1_f32,
+            yz in -1_f32..1_f32
+        ) {
+            // Displacing a point along the third lattice vector only changes the z coordinate
+            let cell = [2., 3., 4., xy, xz, yz];
+            let point = [x, y, z];
+            let displaced = [x + dz * xz * 4., y + dz * yz * 4., z + dz * 4.];
+
+            let original = make_fractional(&cell, &point);
+            let moved = make_fractional(&cell, &displaced);
+            assert_abs_diff_eq!(moved[0], original[0], epsilon = 1e-5);
+            assert_abs_diff_eq!(moved[1], original[1], epsilon = 1e-5);
+            assert_abs_diff_eq!(moved[2], original[2] + dz, epsilon = 1e-5);
+        }
+    }
 }
@@ -0,0 +1,121 @@
+//
+// dynamics.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Quantities describing how a configuration changes over time
+//!
+//! These compare the values computed from two frames of a trajectory, with the particles matched
+//! by their index, which is constant over a simulation.
+
+use crate::frame::Frame;
+use anyhow::{bail, Error};
+
+/// Compute the change of a per-particle order parameter between two frames
+///
+/// The result is `b - a` for each particle, so a positive value is an increase in the order from
+/// frame `a` to frame `b`. This returns an error when the frames have a different number of
+/// particles, since the particles can't be matched.
+///
+pub fn order_change(a: &[f32], b: &[f32]) -> Result<Vec<f32>, Error> {
+    if a.len() != b.len() {
+        bail!(
+            "The frames have different numbers of particles, {} and {}",
+            a.len(),
+            b.len()
+        );
+    }
+    Ok(a.iter().zip(b).map(|(a, b)| b - a).collect())
+}
+
+/// Compute the self-intermediate scattering function between a reference and a later frame
+///
+/// $$ F_s(q, t) = \frac{1}{N} \sum_i \cos(\mathbf{q} \cdot \Delta \mathbf{r}_i) $$
+///
+/// where the wave vector $\mathbf{q}$ of magnitude `q` is along the x axis of the simulation cell
+/// and $\Delta \mathbf{r}_i$ is the displacement of each particle using the unwrapped positions.
+/// This is 1 when no particles have moved, decaying to 0 as the particles move distances larger
+/// than $2\pi / q$.
+///
+pub fn self_intermediate_scattering(
+    reference: &Frame,
+    current: &Frame,
+    q: f32,
+) -> Result<f32, Error> {
+    if reference.len() != current.len() {
+        bail!(
+            "The frames have different numbers of particles, {} and {}",
+            reference.len(),
+            current.len()
+        );
+    }
+    let total: f32 = reference
+        .unwrapped_positions()
+        .iter()
+        .zip(current.unwrapped_positions().iter())
+        .map(|(r, c)| (q * (c.x - r.x)).cos())
+        .sum();
+    Ok(total / reference.len() as f32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+    use gsd::GSDFrame;
+
+    #[test]
+    fn order_change_identical() -> Result<(), Error> {
+        let order = [0.1, 0.5, 0.9];
+        assert_eq!(order_change(&order, &order)?, vec![0.; 3]);
+        Ok(())
+    }
+
+    #[test]
+    fn order_change_direction() -> Result<(), Error> {
+        assert_eq!(order_change(&[0.25, 1.], &[0.75, 0.5])?, vec![0.5, -0.5]);
+        Ok(())
+    }
+
+    #[test]
+    fn order_change_mismatch() {
+        assert!(order_change(&[0.1, 0.2], &[0.1]).is_err());
+    }
+
+    #[test]
+    fn scattering_displacement() -> Result<(), Error> {
+        let position: Vec<[f32; 3]> = (0..10)
+            .map(|i| [i as f32 - 4.3, 0.3 * i as f32, 0.])
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let cell = [10., 10., 1., 0., 0., 0.];
+        let reference: Frame = GSDFrame::from_arrays(position, orientation, cell).into();
+        let q = 2. * std::f32::consts::PI;
+        assert_abs_diff_eq!(self_intermediate_scattering(&reference, &reference, q)?, 1.);
+
+        // Every particle moves by pi / q, with the last crossing the boundary of the cell
+        let mut moved = GSDFrame::from(&reference);
+        for (position, image) in moved.position.iter_mut().zip(moved.image.iter_mut()) {
+            position[0] += 0.5;
+            if position[0] > 5. {
+                position[0] -= 10.;
+                image[0] += 1;
+            }
+        }
+        let current: Frame = moved.into();
+        let value = self_intermediate_scattering(&reference, &current, q)?;
+        assert_abs_diff_eq!(value, -1., epsilon = 1e-5);
+        Ok(())
+    }
+
+    #[test]
+    fn scattering_mismatch() {
+        let frame = |n: usize| -> Frame {
+            let position = vec![[0., 0., 0.]; n];
+            let orientation = vec![[1., 0., 0., 0.]; n];
+            GSDFrame::from_arrays(position, orientation, [10., 10., 1., 0., 0., 0.]).into()
+        };
+        assert!(self_intermediate_scattering(&frame(2), &frame(3), 1.).is_err());
+    }
+}
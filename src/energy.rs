@@ -0,0 +1,57 @@
+//
+// energy.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! The energy of a configuration from the interactions between the particles
+
+use crate::frame::Frame;
+
+/// Compute the total energy of a frame from a pair potential
+///
+/// This sums `potential(r)` over every unique pair of particles with a separation $r$ within
+/// `cutoff`, taking the periodic boundary into account. Each pair is only counted once, so the
+/// potential is the full interaction energy of the pair.
+///
+pub fn pair_energy<F: Fn(f32) -> f32>(frame: &Frame, cutoff: f32, potential: F) -> f32 {
+    frame
+        .neighbours_cutoff(cutoff)
+        .enumerate()
+        .flat_map(|(index, neighbours)| {
+            neighbours
+                .filter(move |&neighbour| neighbour > index)
+                .map(move |neighbour| (index, neighbour))
+        })
+        .map(|(index, neighbour)| potential(frame.neighbour_vector(index, neighbour).norm()))
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::square_lattice;
+    use approx::assert_abs_diff_eq;
+    use gsd::GSDFrame;
+
+    #[test]
+    fn constant_potential() {
+        // Each particle has 4 neighbours, with each pair shared by two particles
+        let frame = square_lattice(5);
+        let energy = pair_energy(&frame, 1.2, |_| 2.);
+        assert_abs_diff_eq!(energy, 2. * (25 * 4 / 2) as f32);
+    }
+
+    #[test]
+    fn lennard_jones_minimum() {
+        let r_min = 2_f32.powf(1. / 6.);
+        let lennard_jones = |r: f32| 4. * (r.powi(-12) - r.powi(-6));
+        let frame: Frame = GSDFrame::from_arrays(
+            vec![[0.; 3], [r_min, 0., 0.]],
+            vec![[1., 0., 0., 0.]; 2],
+            [10., 10., 1., 0., 0., 0.],
+        )
+        .into();
+        assert_abs_diff_eq!(pair_energy(&frame, 2.5, lennard_jones), -1., epsilon = 1e-5);
+    }
+}
@@ -6,35 +6,153 @@
 
 //! A frame type with a number of useful functions
 
-use crate::distance::min_image;
-use gsd::GSDFrame;
-use nalgebra::{Point3, Quaternion, UnitQuaternion, Vector4};
-use rstar::{PointDistance, RTree, RTreeObject, AABB};
+use crate::cell::SimulationCell;
+use crate::distance::{min_image, min_image_with_shift};
+use anyhow::{bail, Error};
+use gsd::{GSDFrame, GSDTrajectory};
+use itertools::iproduct;
+use nalgebra::{Matrix3, Point3, Quaternion, UnitQuaternion, Vector3};
+use rstar::{DefaultParams, PointDistance, RTree, RTreeObject, RTreeParams, AABB};
+use serde::de::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 
-#[derive(Clone, Debug)]
-pub struct Frame {
+/// A configuration of particles, along with a tree of the positions for finding neighbours
+///
+/// The parameters `P` control the size of the nodes of the tree, which can be tuned for the
+/// performance of the neighbour queries using [`Frame::from_with_params`].
+///
+#[derive(Clone)]
+pub struct Frame<P: RTreeParams = DefaultParams> {
     pub timestep: u64,
     pub position: Vec<Point3<f32>>,
     pub orientation: Vec<UnitQuaternion<f32>>,
     pub image: Vec<[i32; 3]>,
+    /// The index of the type of each particle
+    pub typeid: Vec<u32>,
+    /// The rigid body each particle belongs to, which is -1 for particles not in a body
+    pub body: Vec<i32>,
     pub simulation_cell: [f32; 6],
+    /// The number of dimensions recorded in the file the frame was read from
+    pub dimensions: Option<u8>,
+    /// The indices of the pair of particles in each bond
+    pub bonds: Vec<[u32; 2]>,
+    /// Additional named values for each particle, like the results of an analysis
+    pub properties: HashMap<String, Vec<f32>>,
 
-    neighbour_tree: RTree<Position>,
+    neighbour_tree: RTree<Position, P>,
+    image_margin: f32,
+    neighbour_cache: NeighbourCache,
+}
+
+// The parameters of the tree don't implement Debug, so this can't be derived
+impl<P: RTreeParams> std::fmt::Debug for Frame<P> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Frame")
+            .field("timestep", &self.timestep)
+            .field("position", &self.position)
+            .field("orientation", &self.orientation)
+            .field("image", &self.image)
+            .field("typeid", &self.typeid)
+            .field("body", &self.body)
+            .field("simulation_cell", &self.simulation_cell)
+            .field("dimensions", &self.dimensions)
+            .field("bonds", &self.bonds)
+            .field("properties", &self.properties)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The parameters of a query for the neighbours of every particle
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum NeighbourQuery {
+    Count(usize),
+    // The bits of the cutoff, since floats can't be used as a key
+    Cutoff(u32),
+}
+
+/// The neighbour lists of a frame which have already been computed
+///
+/// The cache is behind a Mutex so a Frame can still be shared between threads, with any thread
+/// computing a neighbour list blocking other threads from accessing the cache until it is
+/// complete. Cloning a frame creates an empty cache.
+///
+#[derive(Debug, Default)]
+struct NeighbourCache(Mutex<HashMap<NeighbourQuery, Arc<Vec<Vec<usize>>>>>);
+
+impl Clone for NeighbourCache {
+    fn clone(&self) -> Self {
+        NeighbourCache::default()
+    }
+}
+
+impl NeighbourCache {
+    fn get_or_insert_with(
+        &self,
+        query: NeighbourQuery,
+        compute: impl FnOnce() -> Vec<Vec<usize>>,
+    ) -> Arc<Vec<Vec<usize>>> {
+        let mut cache = self.0.lock().expect("Neighbour cache has been poisoned");
+        cache
+            .entry(query)
+            .or_insert_with(|| Arc::new(compute()))
+            .clone()
+    }
 }
 
 impl From<GSDFrame> for Frame {
     fn from(frame: GSDFrame) -> Frame {
-        // Preconvert the orientations to a quaternion representation
+        Frame::<DefaultParams>::from_with_params(frame)
+    }
+}
+
+/// Convert a frame back to the values stored in a gsd file, for writing with a `GSDWriter`
+///
+/// The orientations are stored with the scalar component first. The values which aren't part of
+/// a [`Frame`], like the diameter and charge, take the defaults of [`GSDFrame::from_arrays`].
+///
+impl<P: RTreeParams> From<&Frame<P>> for GSDFrame {
+    fn from(frame: &Frame<P>) -> GSDFrame {
+        let position = frame.position.iter().map(|p| p.coords.into()).collect();
+        let orientation = frame
+            .orientation
+            .iter()
+            .map(|q| [q.w, q.i, q.j, q.k])
+            .collect();
+        GSDFrame {
+            timestep: frame.timestep,
+            image: frame.image.clone(),
+            typeid: frame.typeid.clone(),
+            body: frame.body.clone(),
+            dimensions: frame.dimensions,
+            bonds: frame.bonds.clone(),
+            ..GSDFrame::from_arrays(position, orientation, frame.simulation_cell)
+        }
+    }
+}
+
+impl<P: RTreeParams> Frame<P> {
+    /// Create a frame with a neighbour tree using the parameters `P`
+    ///
+    /// The parameters are a type implementing [`RTreeParams`], which sets the minimum and maximum
+    /// number of children of each node of the tree, and are chosen with the type of the frame,
+    /// like `Frame::<LargeNodes>::from_with_params(frame)`. Frames created using [`Frame::from`]
+    /// use the default parameters of rstar, which are the only frames supported by the analysis
+    /// functions.
+    ///
+    pub fn from_with_params(frame: GSDFrame) -> Frame<P> {
+        // Preconvert the orientations to a quaternion representation. The gsd file stores the
+        // scalar component first, while nalgebra stores it last.
         let orientation: Vec<UnitQuaternion<f32>> = frame
             .orientation
             .into_iter()
-            .map(Vector4::from)
-            .map(Quaternion::from)
+            .map(|[w, x, y, z]| Quaternion::new(w, x, y, z))
             .map(UnitQuaternion::from_quaternion)
             .collect();
 
-        let neighbour_tree =
-            RTree::bulk_load(array_to_points(&frame.position, &frame.simulation_cell));
+        let (points, image_margin) = periodic_points(&frame.position, &frame.simulation_cell);
+        let neighbour_tree = RTree::bulk_load_with_params(points);
 
         let position: Vec<Point3<f32>> = frame.position.into_iter().map(Point3::from).collect();
 
@@ -43,36 +161,482 @@ impl From<GSDFrame> for Frame {
             position,
             orientation,
             image: frame.image,
+            typeid: frame.typeid,
+            body: frame.body,
             simulation_cell: frame.simulation_cell,
+            dimensions: frame.dimensions,
+            bonds: frame.bonds,
+            properties: HashMap::new(),
             neighbour_tree,
+            image_margin,
+            neighbour_cache: NeighbourCache::default(),
+        }
+    }
+}
+
+/// The values of a [`Frame`] which are serialized, excluding the neighbour tree and cache
+///
+/// The orientations are stored with the scalar component first, the same as the gsd file.
+///
+#[derive(Serialize, Deserialize)]
+struct FrameData {
+    timestep: u64,
+    position: Vec<[f32; 3]>,
+    orientation: Vec<[f32; 4]>,
+    image: Vec<[i32; 3]>,
+    typeid: Vec<u32>,
+    body: Vec<i32>,
+    simulation_cell: [f32; 6],
+    dimensions: Option<u8>,
+    bonds: Vec<[u32; 2]>,
+    properties: HashMap<String, Vec<f32>>,
+}
+
+impl Serialize for Frame {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        FrameData {
+            timestep: self.timestep,
+            position: self.position.iter().map(|p| p.coords.into()).collect(),
+            orientation: self
+                .orientation
+                .iter()
+                .map(|q| [q.w, q.i, q.j, q.k])
+                .collect(),
+            image: self.image.clone(),
+            typeid: self.typeid.clone(),
+            body: self.body.clone(),
+            simulation_cell: self.simulation_cell,
+            dimensions: self.dimensions,
+            bonds: self.bonds.clone(),
+            properties: self.properties.clone(),
         }
+        .serialize(serializer)
     }
 }
 
+/// The neighbour tree is constructed from the positions when deserializing a frame
+///
+/// Like [`Frame::try_new`], the positions are checked to be finite before constructing the tree,
+/// with the orientations normalised the same as a frame read from a gsd file.
+///
+impl<'de> Deserialize<'de> for Frame {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = FrameData::deserialize(deserializer)?;
+        check_finite(data.position.iter().copied(), data.timestep).map_err(D::Error::custom)?;
+        let (points, image_margin) = periodic_points(&data.position, &data.simulation_cell);
+        let neighbour_tree = RTree::bulk_load(points);
+        Ok(Frame {
+            timestep: data.timestep,
+            position: data.position.into_iter().map(Point3::from).collect(),
+            orientation: data
+                .orientation
+                .into_iter()
+                .map(|[w, x, y, z]| UnitQuaternion::from_quaternion(Quaternion::new(w, x, y, z)))
+                .collect(),
+            image: data.image,
+            typeid: data.typeid,
+            body: data.body,
+            simulation_cell: data.simulation_cell,
+            dimensions: data.dimensions,
+            bonds: data.bonds,
+            properties: data.properties,
+            neighbour_tree,
+            image_margin,
+            neighbour_cache: NeighbourCache::default(),
+        })
+    }
+}
+
+/// Return an error listing the indices of any of the positions which are not finite
+fn check_finite(position: impl Iterator<Item = [f32; 3]>, timestep: u64) -> Result<(), Error> {
+    let invalid: Vec<usize> = position
+        .enumerate()
+        .filter(|(_, p)| !p.iter().all(|x| x.is_finite()))
+        .map(|(index, _)| index)
+        .collect();
+    if !invalid.is_empty() {
+        bail!(
+            "The particles {:?} in frame at timestep {} have non-finite positions",
+            invalid,
+            timestep
+        );
+    }
+    Ok(())
+}
+
 impl Frame {
+    /// Create a frame, returning an error when any of the positions are not finite
+    ///
+    /// The neighbour tree can't be constructed from positions which are NaN, so unlike
+    /// [`Frame::from`], which panics for these frames, this checks the positions first. This is
+    /// the same check as [`Frame::validate`].
+    ///
+    pub fn try_new(frame: GSDFrame) -> Result<Frame, Error> {
+        check_finite(frame.position.iter().copied(), frame.timestep)?;
+        Ok(Frame::from(frame))
+    }
+
+    /// Create a frame where the positions may be outside the simulation cell
+    ///
+    /// When `wrapped` is false the positions are treated as unwrapped coordinates, which are
+    /// moved into the simulation cell before constructing the neighbour tree, with the periodic
+    /// images moved by each particle added to the image. Otherwise this is the same as
+    /// [`Frame::from`], which expects the positions to already be within the cell.
+    ///
+    pub fn from_gsd(mut frame: GSDFrame, wrapped: bool) -> Frame {
+        if !wrapped {
+            for (position, image) in frame.position.iter_mut().zip(frame.image.iter_mut()) {
+                let (inside, shift) = min_image_with_shift(&frame.simulation_cell, position);
+                *position = inside;
+                for (i, s) in image.iter_mut().zip(shift.iter()) {
+                    *i += s;
+                }
+            }
+        }
+        Frame::from(frame)
+    }
+}
+
+impl<P: RTreeParams> Frame<P> {
+    /// The `n` nearest neighbours of every particle, ordered by distance
+    ///
+    /// Like [`Frame::particle_neighbours_n`], neighbours across the periodic boundary are only
+    /// found within the [`Frame::image_margin`].
+    ///
     pub fn neighbours_n<'a>(
         &'a self,
         n: usize,
     ) -> impl Iterator<Item = impl Iterator<Item = usize> + 'a> + '_ {
-        self.position.iter().map(move |&point| {
+        (0..self.len()).map(move |index| self.particle_neighbours_n(index, n))
+    }
+
+    /// The position of a particle wrapped into the simulation cell, which is used for the queries
+    fn query_point(&self, index: usize) -> [f32; 3] {
+        min_image(&self.simulation_cell, &self.position[index].coords.into())
+    }
+
+    /// The distance across the periodic boundary within which the neighbour queries are exact
+    ///
+    /// Periodic images are only created for the particles within 4 times the average spacing
+    /// between particles of each face of the cell, so a neighbour further than this across the
+    /// boundary is never found. This is infinite when the cell is small enough that every
+    /// particle has a periodic image along each axis.
+    ///
+    pub fn image_margin(&self) -> f32 {
+        self.image_margin
+    }
+
+    /// The tree of particle positions used for the neighbour queries
+    ///
+    /// This allows for custom spatial queries using the methods of [`RTree`]. The tree contains
+    /// the positions wrapped into the simulation cell, along with the periodic images of the
+    /// particles near each face of the cell, so a single particle can appear more than once in the
+    /// results of a query.
+    ///
+    /// ```
+    /// use gsd::GSDFrame;
+    /// use rstar::AABB;
+    /// use trajedy::frame::Frame;
+    ///
+    /// let position = vec![[0., 0., 0.], [1., 0., 0.], [3., 3., 0.]];
+    /// let orientation = vec![[1., 0., 0., 0.]; 3];
+    /// let frame: Frame = GSDFrame::from_arrays(position, orientation, [10., 10., 1., 0., 0., 0.]).into();
+    ///
+    /// let envelope = AABB::from_corners([-0.5, -0.5, -0.5], [1.5, 0.5, 0.5]);
+    /// let mut found: Vec<usize> = frame
+    ///     .neighbour_tree()
+    ///     .locate_in_envelope(&envelope)
+    ///     .map(|p| p.index)
+    ///     .collect();
+    /// found.sort_unstable();
+    /// assert_eq!(found, vec![0, 1]);
+    /// ```
+    ///
+    pub fn neighbour_tree(&self) -> &RTree<Position, P> {
+        &self.neighbour_tree
+    }
+
+    /// The `n` nearest neighbours of a single particle, searching over every particle in the frame
+    ///
+    /// The particle itself is not one of its neighbours, so every index is different to `index`.
+    /// Neighbours across the periodic boundary are only found within the [`Frame::image_margin`],
+    /// so the `n` nearest neighbours are only exact when they are all within this distance.
+    ///
+    pub fn particle_neighbours_n(
+        &self,
+        index: usize,
+        n: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        unique_indices(
             self.neighbour_tree
-                .nearest_neighbor_iter(&point.coords.into())
-                .take(n)
-                .map(|i| i.index)
-        })
+                .nearest_neighbor_iter(&self.query_point(index)),
+        )
+        .filter(move |&neighbour| neighbour != index)
+        .take(n)
+    }
+
+    /// The `n` particles nearest to an arbitrary point, taking the periodic boundary into account
+    ///
+    /// As with [`Frame::particle_neighbours_n`], particles across the periodic boundary are only
+    /// found within the [`Frame::image_margin`] of the point.
+    ///
+    pub fn nearest_to(&self, point: &[f32; 3], n: usize) -> Vec<usize> {
+        unique_indices(
+            self.neighbour_tree
+                .nearest_neighbor_iter(&min_image(&self.simulation_cell, point)),
+        )
+        .take(n)
+        .collect()
+    }
+
+    /// The vector from a particle to a neighbour, taking the periodic boundary into account
+    pub fn neighbour_vector(&self, index: usize, neighbour: usize) -> Vector3<f32> {
+        let difference = self.position[neighbour] - self.position[index];
+        Vector3::from(min_image(&self.simulation_cell, &difference.into()))
+    }
+
+    /// The vectors from a particle to each of its `n` nearest neighbours
+    pub fn neighbour_vectors_n(&self, index: usize, n: usize) -> Vec<Vector3<f32>> {
+        self.particle_neighbours_n(index, n)
+            .map(|neighbour| self.neighbour_vector(index, neighbour))
+            .collect()
+    }
+
+    /// The `n` nearest neighbours of a single particle which have the same type as the particle
+    pub fn particle_neighbours_same_type(
+        &self,
+        index: usize,
+        n: usize,
+    ) -> impl Iterator<Item = usize> + '_ {
+        unique_indices(
+            self.neighbour_tree
+                .nearest_neighbor_iter(&self.query_point(index))
+                .filter(move |p| self.typeid[p.index] == self.typeid[index]),
+        )
+        .filter(move |&neighbour| neighbour != index)
+        .take(n)
+    }
+
+    /// The second shell of neighbours for each particle in the frame
+    ///
+    /// The first shell comprises the `n` nearest neighbours of a particle, while the second shell
+    /// are the `n` nearest neighbours of each particle within the first shell. Both the central
+    /// particle and those in the first shell are excluded from the second shell, with the
+    /// remaining indices sorted and deduplicated.
+    ///
+    pub fn second_neighbours_n(&self, n: usize) -> Vec<Vec<usize>> {
+        (0..self.len())
+            .map(|index| {
+                let first: Vec<usize> = self.particle_neighbours_n(index, n).collect();
+                let mut second: Vec<usize> = first
+                    .iter()
+                    .flat_map(|&i| self.particle_neighbours_n(i, n))
+                    .filter(|i| *i != index && !first.contains(i))
+                    .collect();
+                second.sort_unstable();
+                second.dedup();
+                second
+            })
+            .collect()
     }
 
+    /// The neighbours of every particle within a distance of `cutoff`, excluding the particle itself
+    ///
+    /// A particle at exactly the distance `cutoff` is a neighbour, which is the same boundary used
+    /// for every other query with a cutoff. The `cutoff` can be no larger than the
+    /// [`Frame::image_margin`], since neighbours beyond this distance across the periodic
+    /// boundary are missed.
+    ///
     pub fn neighbours_cutoff<'a>(
         &'a self,
         cutoff: f32,
     ) -> impl Iterator<Item = impl Iterator<Item = usize> + 'a> + '_ {
-        self.position.iter().map(move |&point| {
-            self.neighbour_tree
-                .locate_within_distance(point.coords.into(), cutoff * cutoff)
-                .map(|i| i.index)
+        debug_assert!(
+            cutoff <= self.image_margin,
+            "The cutoff {} is larger than the image margin {} of the frame",
+            cutoff,
+            self.image_margin
+        );
+        (0..self.len()).map(move |index| {
+            unique_indices(
+                self.neighbour_tree
+                    .locate_within_distance(self.query_point(index), cutoff * cutoff),
+            )
+            .filter(move |&neighbour| neighbour != index)
         })
     }
 
+    /// Check the positions of all the particles are finite values
+    ///
+    /// A simulation which has become unstable can produce positions which are NaN or infinite,
+    /// which give meaningless results for any analysis. This returns an error listing the indices
+    /// of all the particles with a non-finite position.
+    ///
+    pub fn validate(&self) -> Result<(), Error> {
+        check_finite(self.position.iter().map(|p| p.coords.into()), self.timestep)
+    }
+
+    /// The `n` nearest neighbours of every particle, which are cached for subsequent calls
+    ///
+    /// The lists are computed from the positions when the frame was created, so modifying the
+    /// positions of the particles doesn't change the neighbours.
+    ///
+    pub fn neighbour_list_n(&self, n: usize) -> Arc<Vec<Vec<usize>>> {
+        self.neighbour_cache
+            .get_or_insert_with(NeighbourQuery::Count(n), || {
+                self.neighbours_n(n)
+                    .map(|neighs| neighs.collect())
+                    .collect()
+            })
+    }
+
+    /// The neighbours of every particle within `cutoff`, which are cached for subsequent calls
+    pub fn neighbour_list_cutoff(&self, cutoff: f32) -> Arc<Vec<Vec<usize>>> {
+        self.neighbour_cache
+            .get_or_insert_with(NeighbourQuery::Cutoff(cutoff.to_bits()), || {
+                self.neighbours_cutoff(cutoff)
+                    .map(|neighs| neighs.collect())
+                    .collect()
+            })
+    }
+
+    /// The `n` nearest neighbours of every particle as a sorted list of indices
+    ///
+    /// Unlike [`Frame::neighbours_n`], where the neighbours are ordered by distance, each list is
+    /// sorted by index without any duplicates, which is convenient for comparing the neighbours or
+    /// performing set operations.
+    ///
+    pub fn neighbour_sets_n(&self, n: usize) -> Vec<Vec<usize>> {
+        self.neighbours_n(n)
+            .map(|neighs| {
+                let mut neighs: Vec<usize> = neighs.collect();
+                neighs.sort_unstable();
+                neighs.dedup();
+                neighs
+            })
+            .collect()
+    }
+
+    /// The `n` nearest neighbours of every particle which are not bonded to the particle
+    ///
+    /// Only the bonds directly between two particles are excluded, with the next nearest particles
+    /// taking their place, so each particle still has `n` neighbours.
+    ///
+    pub fn neighbours_n_excluding_bonded(&self, n: usize) -> Vec<Vec<usize>> {
+        let mut bonded: HashMap<usize, Vec<usize>> = HashMap::new();
+        for &[a, b] in self.bonds.iter() {
+            bonded.entry(a as usize).or_default().push(b as usize);
+            bonded.entry(b as usize).or_default().push(a as usize);
+        }
+        (0..self.len())
+            .map(|index| {
+                let partners = bonded.get(&index).map_or(&[][..], |p| p.as_slice());
+                self.particle_neighbours_n(index, n + partners.len())
+                    .filter(|neighbour| !partners.contains(neighbour))
+                    .take(n)
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The rigid body each particle belongs to
+    pub fn bodies(&self) -> &[i32] {
+        &self.body
+    }
+
+    /// The indices of the particles within each rigid body, keyed by the body id
+    ///
+    /// Particles which are not part of a rigid body, having a negative body id, are not included.
+    ///
+    pub fn group_by_body(&self) -> HashMap<i32, Vec<usize>> {
+        let mut groups: HashMap<i32, Vec<usize>> = HashMap::new();
+        for (index, &body) in self.body.iter().enumerate().filter(|(_, &b)| b >= 0) {
+            groups.entry(body).or_default().push(index);
+        }
+        groups
+    }
+
+    /// Attach a named value to each particle, replacing any existing values with that name
+    ///
+    /// This returns an error when there isn't a value for each particle in the frame.
+    ///
+    pub fn set_property(&mut self, name: &str, values: Vec<f32>) -> Result<(), Error> {
+        if values.len() != self.len() {
+            bail!(
+                "Property '{}' has {} values, while the frame has {} particles",
+                name,
+                values.len(),
+                self.len()
+            );
+        }
+        self.properties.insert(name.to_string(), values);
+        Ok(())
+    }
+
+    pub fn get_property(&self, name: &str) -> Option<&[f32]> {
+        self.properties.get(name).map(|v| v.as_slice())
+    }
+
+    /// The number of dimensions of the configuration, either 2 or 3
+    ///
+    /// This uses the dimensions recorded in the file where they are available. Otherwise, the
+    /// configuration is two dimensional when all the particles have the same z position, with the
+    /// cell having a z length of at most 1, which is the convention used by hoomd for 2D cells.
+    ///
+    pub fn effective_dimensions(&self) -> usize {
+        if let Some(dimensions) = self.dimensions {
+            return dimensions as usize;
+        }
+        let z = self.position.first().map_or(0., |p| p.z);
+        let planar = self.position.iter().all(|p| (p.z - z).abs() < 1e-6);
+        if planar && self.simulation_cell[2] <= 1. {
+            2
+        } else {
+            3
+        }
+    }
+
+    /// The rotation matrix of each particle, equivalent to the orientation quaternion
+    pub fn orientation_matrices(&self) -> Vec<Matrix3<f32>> {
+        self.orientation
+            .iter()
+            .map(|q| q.to_rotation_matrix().into_inner())
+            .collect()
+    }
+
+    /// The positions with the periodic image of each particle removed
+    ///
+    /// These are the positions of the particles had they not been wrapped into the simulation
+    /// cell, so the displacement between two frames includes the movement across the boundaries.
+    ///
+    pub fn unwrapped_positions(&self) -> Vec<Point3<f32>> {
+        let cell = SimulationCell::from(self.simulation_cell).to_matrix();
+        self.position
+            .iter()
+            .zip(self.image.iter())
+            .map(|(position, image)| {
+                position + cell * Vector3::new(image[0] as f32, image[1] as f32, image[2] as f32)
+            })
+            .collect()
+    }
+
+    /// The volume of the simulation cell, which is independent of the tilt factors
+    pub fn volume(&self) -> f32 {
+        SimulationCell::from(self.simulation_cell).volume()
+    }
+
+    /// The number of particles per unit volume of the simulation cell
+    pub fn number_density(&self) -> f32 {
+        self.len() as f32 / self.volume()
+    }
+
+    /// The indices of the particles whose position, wrapped into the simulation cell, is in `region`
+    pub fn indices_in_region(&self, region: &Region) -> Vec<usize> {
+        (0..self.len())
+            .filter(|&index| region.contains(&self.query_point(index)))
+            .collect()
+    }
+
     pub fn len(&self) -> usize {
         self.position.len()
     }
@@ -82,10 +646,48 @@ impl Frame {
     }
 }
 
+/// Read the frames of a trajectory ready for analysis
+///
+/// Iterating over a [`GSDTrajectory`] gives the values as they are stored in the file, while this
+/// converts each frame to a [`Frame`], constructing the tree used for the neighbour queries.
+///
+pub trait ProcessedFrames {
+    /// Read and convert every frame of the trajectory in order
+    fn frames_processed(&self) -> impl Iterator<Item = Result<Frame, Error>> + '_;
+}
+
+impl ProcessedFrames for GSDTrajectory {
+    fn frames_processed(&self) -> impl Iterator<Item = Result<Frame, Error>> + '_ {
+        (0..self.nframes()).map(move |index| Frame::try_new(self.get_frame(index)?))
+    }
+}
+
+/// A region of the simulation cell for selecting a subset of the particles
 #[derive(Clone, Copy, Debug, PartialEq)]
-struct Position {
-    point: [f32; 3],
-    index: usize,
+pub enum Region {
+    /// The particles with a coordinate along `axis` (0, 1 or 2 for x, y or z) in `[min, max)`
+    Slab { axis: usize, min: f32, max: f32 },
+    /// The particles within an axis aligned box, including the corner `min` excluding `max`
+    Box { min: [f32; 3], max: [f32; 3] },
+}
+
+impl Region {
+    /// Whether the point is within the region
+    pub fn contains(&self, point: &[f32; 3]) -> bool {
+        match self {
+            Region::Slab { axis, min, max } => *min <= point[*axis] && point[*axis] < *max,
+            Region::Box { min, max } => (0..3).all(|i| min[i] <= point[i] && point[i] < max[i]),
+        }
+    }
+}
+
+/// A point within the neighbour tree, which is either a particle or a periodic image of one
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Position {
+    /// The location of the point in cartesian coordinates
+    pub point: [f32; 3],
+    /// The index of the particle in the frame
+    pub index: usize,
     cell: [f32; 6],
 }
 
@@ -121,7 +723,7 @@ impl PointDistance for Position {
 
     fn distance_2_if_less_or_equal(&self, point: &[f32; 3], max_distance_2: f32) -> Option<f32> {
         match self.distance_2(point) {
-            d if d < max_distance_2 => Some(d),
+            d if d <= max_distance_2 => Some(d),
             _ => None,
         }
     }
@@ -133,19 +735,569 @@ impl PointDistance for Position {
     }
 }
 
-fn array_to_points(array: &[[f32; 3]], cell: &[f32; 6]) -> Vec<Position> {
-    array
-        // Iterate over the rows
+/// The number of inter-particle spacings from each face of the cell that periodic images cover
+const IMAGE_MARGIN: f32 = 4.;
+
+/// The distance from each face of the cell for which periodic images of particles are created
+///
+/// This scales with the average spacing between particles, which for a 2D configuration (all the
+/// particles having the same z position) only considers the x and y dimensions.
+///
+// I want to only compare the values exactly here
+#[allow(clippy::float_cmp)]
+fn image_margin(array: &[[f32; 3]], cell: &SimulationCell) -> f32 {
+    if array.is_empty() {
+        return 0.;
+    }
+    let num_particles = array.len() as f32;
+    let spacing = if array.iter().all(|p| p[2] == array[0][2]) {
+        (cell.lx * cell.ly / num_particles).sqrt()
+    } else {
+        (cell.volume() / num_particles).cbrt()
+    };
+    IMAGE_MARGIN * spacing
+}
+
+/// Create the points to insert into the neighbour tree
+///
+/// The R-tree only understands the cartesian space, so it can't find neighbours across the
+/// periodic boundary. To work around this, all the points are wrapped into the simulation cell,
+/// and periodic images are added of the particles near each face. The images share the index of
+/// the original particle, so the neighbour queries have to remove duplicate indices.
+///
+/// Along with the points this returns the distance from each particle within which the neighbours
+/// found are correct, which is the [`image_margin`] unless every particle has an image along
+/// every axis, in which case it is infinite.
+///
+fn periodic_points(array: &[[f32; 3]], cell: &[f32; 6]) -> (Vec<Position>, f32) {
+    let simulation_cell = SimulationCell::from(*cell);
+    let lattice = simulation_cell.to_matrix();
+    let margin = image_margin(array, &simulation_cell);
+    // The margin is measured perpendicular to each face, which for a tilted cell is closer than
+    // the length of the lattice vector
+    let fraction_margin: Vec<f32> = simulation_cell
+        .heights()
         .iter()
-        .enumerate()
-        // Convert from slice to owned array
-        .map(|(index, row)| Position::new(row, index, cell))
-        .collect()
+        .map(|h| (margin / h).min(0.5))
+        .collect();
+    let covered = if array.is_empty() || fraction_margin.iter().all(|&m| m >= 0.5) {
+        f32::INFINITY
+    } else {
+        margin
+    };
+
+    let mut points = Vec::with_capacity(array.len());
+    for (index, row) in array.iter().enumerate() {
+        let wrapped = simulation_cell.min_image(row);
+        points.push(Position::new(&wrapped, index, cell));
+
+        // Find the periodic images along each axis which are within the margin
+        let fractional = simulation_cell.make_fractional(&wrapped);
+        let shifts: Vec<Vec<f32>> = fractional
+            .iter()
+            .zip(fraction_margin.iter())
+            .map(|(&f, &m)| match f {
+                f if f <= m => vec![0., 1.],
+                f if f >= 1. - m => vec![0., -1.],
+                _ => vec![0.],
+            })
+            .collect();
+
+        for (&x, &y, &z) in iproduct!(&shifts[0], &shifts[1], &shifts[2]) {
+            if x == 0. && y == 0. && z == 0. {
+                continue;
+            }
+            let image = Vector3::from(wrapped) + lattice * Vector3::new(x, y, z);
+            points.push(Position::new(&image.into(), index, cell));
+        }
+    }
+    (points, covered)
+}
+
+/// Filter the periodic images from the results of a query, keeping the first instance of each index
+fn unique_indices<'a>(
+    points: impl Iterator<Item = &'a Position> + 'a,
+) -> impl Iterator<Item = usize> + 'a {
+    let mut seen: Vec<usize> = Vec::new();
+    points.map(|p| p.index).filter(move |&index| {
+        if seen.contains(&index) {
+            false
+        } else {
+            seen.push(index);
+            true
+        }
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::{sheared_lattice, square_lattice};
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn group_by_body() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/rigid.gsd");
+        let frame: Frame = gsd::GSDTrajectory::new(filename)?.get_frame(0)?.into();
+        assert_eq!(frame.bodies(), [0, 0, 0, 3, 3, -1]);
+        let groups = frame.group_by_body();
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[&0], vec![0, 1, 2]);
+        assert_eq!(groups[&3], vec![3, 4]);
+
+        // The bodies of the later frames are taken from the first frame
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/first-frame.gsd");
+        let frame: Frame = gsd::GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        let groups = frame.group_by_body();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[&1], vec![1, 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn frames_processed() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let trj = GSDTrajectory::new(filename)?;
+        let mut count = 0;
+        for frame in trj.frames_processed() {
+            let frame = frame?;
+            let neighbours = frame.neighbour_sets_n(6);
+            assert_eq!(neighbours.len(), frame.len());
+            assert!(neighbours.iter().all(|n| n.len() == 6));
+            count += 1;
+        }
+        assert_eq!(count, trj.nframes());
+        Ok(())
+    }
+
+    #[test]
+    fn serde_roundtrip() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let mut frame: Frame = gsd::GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        frame.set_property("index", (0..frame.len()).map(|i| i as f32).collect())?;
+
+        let restored: Frame = serde_json::from_str(&serde_json::to_string(&frame)?)?;
+        assert_eq!(restored.timestep, frame.timestep);
+        assert_eq!(restored.position, frame.position);
+        assert_eq!(restored.orientation, frame.orientation);
+        assert_eq!(restored.simulation_cell, frame.simulation_cell);
+        assert_eq!(restored.properties, frame.properties);
+        // The neighbour tree is rebuilt from the positions
+        assert_eq!(restored.neighbour_sets_n(6), frame.neighbour_sets_n(6));
+        Ok(())
+    }
+
+    #[test]
+    fn deserialize_invalid() -> Result<(), Error> {
+        let mut frame = square_lattice(4);
+        frame.orientation[0] = UnitQuaternion::new_unchecked(Quaternion::new(2., 0., 0., 0.));
+        let restored: Frame = serde_json::from_str(&serde_json::to_string(&frame)?)?;
+        assert_abs_diff_eq!(restored.orientation[0].norm(), 1.);
+
+        // A value too large for an f32 is read as infinite
+        let json = serde_json::to_string(&frame)?.replacen("-2.0", "1e39", 1);
+        let message = serde_json::from_str::<Frame>(&json)
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("non-finite positions"), "{}", message);
+        Ok(())
+    }
+
+    #[test]
+    fn gsd_frame_roundtrip() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/bonded.gsd");
+        let mut original = gsd::GSDTrajectory::new(filename)?.get_frame(0)?;
+        // A different rotation about the z axis for each particle
+        for (index, orientation) in original.orientation.iter_mut().enumerate() {
+            let half_angle = 0.3 * index as f32;
+            *orientation = [half_angle.cos(), 0., 0., half_angle.sin()];
+        }
+        let frame: Frame = original.clone().into();
+
+        let restored = GSDFrame::from(&frame);
+        assert_eq!(restored.timestep, original.timestep);
+        assert_eq!(restored.position, original.position);
+        for (r, o) in restored.orientation.iter().zip(original.orientation.iter()) {
+            for (a, b) in r.iter().zip(o.iter()) {
+                assert_abs_diff_eq!(a, b, epsilon = 1e-6);
+            }
+        }
+        assert_eq!(restored.image, original.image);
+        assert_eq!(restored.typeid, original.typeid);
+        assert_eq!(restored.body, original.body);
+        assert_eq!(restored.simulation_cell, original.simulation_cell);
+        assert_eq!(restored.dimensions, original.dimensions);
+        assert_eq!(restored.bonds, original.bonds);
+        Ok(())
+    }
+
+    #[test]
+    fn from_unwrapped() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let wrapped = gsd::GSDTrajectory::new(filename)?.get_frame(1)?;
+        let cell = SimulationCell::from(wrapped.simulation_cell).to_matrix();
+
+        // Move each particle by a different number of periodic images
+        let mut unwrapped = wrapped.clone();
+        for (index, (position, image)) in unwrapped
+            .position
+            .iter_mut()
+            .zip(unwrapped.image.iter_mut())
+            .enumerate()
+        {
+            let shift = [(index % 7) as i32 - 3, (index % 5) as i32 - 2, 0];
+            let offset = cell * Vector3::new(shift[0] as f32, shift[1] as f32, 0.);
+            *position = (Vector3::from(*position) + offset).into();
+            *image = [image[0] - shift[0], image[1] - shift[1], image[2]];
+        }
+
+        let expected: Frame = wrapped.into();
+        let frame = Frame::from_gsd(unwrapped, false);
+        assert_eq!(frame.neighbour_sets_n(6), expected.neighbour_sets_n(6));
+        assert_eq!(frame.image, expected.image);
+        for (p, e) in frame.position.iter().zip(expected.position.iter()) {
+            assert_abs_diff_eq!(p, e, epsilon = 1e-3);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn from_with_params() {
+        #[derive(Clone, Copy)]
+        struct LargeNodes;
+
+        impl RTreeParams for LargeNodes {
+            const MIN_SIZE: usize = 8;
+            const MAX_SIZE: usize = 24;
+            const REINSERTION_COUNT: usize = 4;
+            type DefaultInsertionStrategy = rstar::RStarInsertionStrategy;
+        }
+
+        let position: Vec<[f32; 3]> = iproduct!(0..12, 0..12)
+            .map(|(x, y)| [x as f32 - 6., y as f32 - 6., 0.])
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame = GSDFrame::from_arrays(position, orientation, [12., 12., 1., 0., 0., 0.]);
+        let large = Frame::<LargeNodes>::from_with_params(frame.clone());
+        let default = Frame::from(frame);
+        assert_eq!(large.neighbour_sets_n(8), default.neighbour_sets_n(8));
+    }
+
+    #[test]
+    fn orientation_matrices() {
+        use nalgebra::Rotation3;
+        use std::f32::consts::FRAC_PI_2;
+
+        // A rotation of 90 degrees about the z axis, stored with the scalar component first
+        let (s, c) = (FRAC_PI_2 / 2.).sin_cos();
+        let frame: Frame = GSDFrame::from_arrays(
+            vec![[0.; 3], [1., 0., 0.]],
+            vec![[1., 0., 0., 0.], [c, 0., 0., s]],
+            [4., 4., 1., 0., 0., 0.],
+        )
+        .into();
+        let matrices = frame.orientation_matrices();
+        assert_eq!(matrices.len(), 2);
+        assert_abs_diff_eq!(matrices[0], Matrix3::identity(), epsilon = 1e-6);
+        #[rustfmt::skip]
+        let expected = Matrix3::new(
+            0., -1., 0.,
+            1., 0., 0.,
+            0., 0., 1.,
+        );
+        assert_abs_diff_eq!(matrices[1], expected, epsilon = 1e-6);
+
+        // Converting the matrix back gives the original orientation
+        let rotation = Rotation3::from_matrix_unchecked(matrices[1]);
+        let q = UnitQuaternion::from_rotation_matrix(&rotation);
+        assert_abs_diff_eq!(q.angle_to(&frame.orientation[1]), 0., epsilon = 1e-3);
+    }
+
+    #[test]
+    fn neighbours_excluding_bonded() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/bonded.gsd");
+        let frame: Frame = gsd::GSDTrajectory::new(filename)?.get_frame(0)?.into();
+        // The bonded particles 0 and 1 are the closest pair in the frame
+        assert_eq!(frame.particle_neighbours_n(0, 1).collect::<Vec<_>>(), [1]);
+
+        let neighbours = frame.neighbours_n_excluding_bonded(2);
+        assert!(!neighbours[0].contains(&1));
+        assert!(!neighbours[1].contains(&0));
+        assert_eq!(neighbours[1], [2, 3]);
+        for neighs in neighbours.iter() {
+            assert_eq!(neighs.len(), 2);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn effective_dimensions() -> Result<(), Error> {
+        assert_eq!(square_lattice(4).effective_dimensions(), 2);
+
+        let position: Vec<[f32; 3]> = iproduct!(0..3, 0..3, 0..3)
+            .map(|(x, y, z)| [x as f32, y as f32, z as f32])
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [3., 3., 3., 0., 0., 0.]).into();
+        assert_eq!(frame.effective_dimensions(), 3);
+
+        // A single layer of particles within a cell which is periodic in z
+        let mut frame = square_lattice(4);
+        frame.simulation_cell[2] = 10.;
+        assert_eq!(frame.effective_dimensions(), 3);
+
+        // The dimensions within the file take precedence
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let mut frame: Frame = gsd::GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        frame.simulation_cell[2] = 10.;
+        assert_eq!(frame.effective_dimensions(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn neighbour_sets_sorted() {
+        let frame = square_lattice(6);
+        for (index, neighs) in frame.neighbour_sets_n(4).iter().enumerate() {
+            assert_eq!(neighs.len(), 4);
+            assert!(neighs.windows(2).all(|w| w[0] < w[1]));
+            assert!(!neighs.contains(&index));
+        }
+        // Requesting more neighbours than there are particles only finds each particle once
+        let frame = square_lattice(2);
+        assert_eq!(
+            frame.neighbour_sets_n(10),
+            vec![vec![1, 2, 3], vec![0, 2, 3], vec![0, 1, 3], vec![0, 1, 2]]
+        );
+    }
+
+    #[test]
+    fn neighbour_list_cached() {
+        let frame = square_lattice(6);
+        let first = frame.neighbour_list_n(5);
+        let second = frame.neighbour_list_n(5);
+        assert!(Arc::ptr_eq(&first, &second));
+        let expected: Vec<Vec<usize>> = frame.neighbours_n(5).map(|n| n.collect()).collect();
+        assert_eq!(*first, expected);
+
+        let cutoff = frame.neighbour_list_cutoff(1.1);
+        assert!(Arc::ptr_eq(&cutoff, &frame.neighbour_list_cutoff(1.1)));
+        assert!(!Arc::ptr_eq(&cutoff, &frame.neighbour_list_cutoff(1.5)));
+        assert_eq!(cutoff[0].len(), 4);
+    }
+
+    #[test]
+    fn volume_density() {
+        let frame = square_lattice(4);
+        assert_eq!(frame.volume(), 16.);
+        assert_eq!(frame.number_density(), 1.);
+
+        let position = vec![[0.; 3]; 6];
+        let orientation = vec![[1., 0., 0., 0.]; 6];
+        let orthorhombic: Frame = GSDFrame::from_arrays(
+            position.clone(),
+            orientation.clone(),
+            [2., 3., 4., 0., 0., 0.],
+        )
+        .into();
+        let tilted: Frame =
+            GSDFrame::from_arrays(position, orientation, [2., 3., 4., 0.5, -0.3, 0.2]).into();
+        assert_eq!(orthorhombic.volume(), 24.);
+        assert_eq!(tilted.volume(), orthorhombic.volume());
+        assert_eq!(tilted.number_density(), 0.25);
+    }
+
+    #[test]
+    fn validate_non_finite() {
+        let position = vec![[0., 0., 0.], [1., 0., 0.], [2., 0., 0.], [3., 0., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let mut frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [10., 10., 1., 0., 0., 0.]).into();
+        // The tree can't be built from non-finite positions, so they are modified afterwards
+        frame.position[1].x = f32::NAN;
+        frame.position[3].z = f32::INFINITY;
+        let message = frame.validate().unwrap_err().to_string();
+        assert!(message.contains("[1, 3]"), "{}", message);
+
+        assert!(square_lattice(4).validate().is_ok());
+    }
+
+    #[test]
+    fn try_new_non_finite() {
+        // Enough particles that building the tree partitions the positions, which panics for NaN
+        let mut position: Vec<[f32; 3]> = (0..20)
+            .flat_map(|y| (0..20).map(move |x| [x as f32 - 10., y as f32 - 10., 0.]))
+            .collect();
+        position[150][1] = f32::NAN;
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame = GSDFrame::from_arrays(position, orientation, [20., 20., 1., 0., 0., 0.]);
+        let message = Frame::try_new(frame.clone()).unwrap_err().to_string();
+        assert!(message.contains("[150]"), "{}", message);
+
+        let mut valid = frame;
+        valid.position[150][1] = 0.;
+        assert_eq!(Frame::try_new(valid).unwrap().len(), 400);
+    }
+
+    #[test]
+    fn orientation_scalar_first() {
+        // A rotation of 90 degrees about the z axis, with the scalar component first
+        let half = std::f32::consts::FRAC_PI_4;
+        let frame: Frame = GSDFrame::from_arrays(
+            vec![[0., 0., 0.]],
+            vec![[half.cos(), 0., 0., half.sin()]],
+            [4., 4., 1., 0., 0., 0.],
+        )
+        .into();
+        let rotated = frame.orientation[0] * Vector3::x();
+        assert_abs_diff_eq!(rotated, Vector3::y(), epsilon = 1e-6);
+    }
+
+    #[test]
+    fn orientation_component_order() {
+        // Distinct components so any permutation of [w, x, y, z] is detected
+        let frame: Frame = GSDFrame::from_arrays(
+            vec![[0., 0., 0.]],
+            vec![[0.5, 0.1, 0.3, 0.8]],
+            [4., 4., 1., 0., 0., 0.],
+        )
+        .into();
+        let expected = Quaternion::new(0.5, 0.1, 0.3, 0.8).normalize();
+        let q = frame.orientation[0].into_inner();
+        assert_abs_diff_eq!(q.w, expected.w, epsilon = 1e-6);
+        assert_abs_diff_eq!(q.i, expected.i, epsilon = 1e-6);
+        assert_abs_diff_eq!(q.j, expected.j, epsilon = 1e-6);
+        assert_abs_diff_eq!(q.k, expected.k, epsilon = 1e-6);
+    }
+
+    #[test]
+    fn nearest_to_centre() {
+        let frame = square_lattice(8);
+        assert_eq!(frame.nearest_to(&[0., 0., 0.], 1), vec![4 * 8 + 4]);
+        // The periodic image of the centre is found from outside the cell
+        assert_eq!(frame.nearest_to(&[8.1, -7.9, 0.], 1), vec![4 * 8 + 4]);
+    }
+
+    #[test]
+    fn second_neighbours_lattice() {
+        let frame = square_lattice(8);
+        // The particle at the origin
+        let centre = 4 * 8 + 4;
+        let first: Vec<usize> = frame.particle_neighbours_n(centre, 4).collect();
+        let second = &frame.second_neighbours_n(4)[centre];
+
+        let mut expected: Vec<usize> = [
+            (2, 4),
+            (3, 3),
+            (3, 5),
+            (4, 2),
+            (4, 6),
+            (5, 3),
+            (5, 5),
+            (6, 4),
+        ]
+        .iter()
+        .map(|(y, x)| y * 8 + x)
+        .collect();
+        expected.sort_unstable();
+
+        assert_eq!(second, &expected);
+        assert!(!second.contains(&centre));
+        for i in first {
+            assert!(!second.contains(&i));
+        }
+    }
+
+    #[test]
+    fn neighbours_across_boundary() {
+        let frame = square_lattice(20);
+        // The particle at the corner of the cell (-10, -10)
+        let mut neighbours: Vec<usize> = frame.particle_neighbours_n(0, 4).collect();
+        neighbours.sort_unstable();
+        assert_eq!(neighbours, vec![1, 19, 20, 380]);
+    }
+
+    #[test]
+    fn image_margin_cutoff() {
+        let frame = square_lattice(20);
+        assert_abs_diff_eq!(frame.image_margin(), 4.);
+        // The particle at (6, -10) is 4 from the corner particle across the boundary
+        let within: Vec<usize> = frame.neighbours_cutoff(4.).next().unwrap().collect();
+        assert!(within.contains(&16));
+
+        // Every particle of a small cell has an image along each axis
+        assert_eq!(square_lattice(4).image_margin(), f32::INFINITY);
+    }
+
+    #[test]
+    fn image_margin_tilted() {
+        let frame = sheared_lattice(20, 0.5);
+        let cutoff = 3.9;
+        for (index, neighbours) in frame.neighbours_cutoff(cutoff).enumerate() {
+            let mut neighbours: Vec<usize> = neighbours.collect();
+            neighbours.sort_unstable();
+            let expected: Vec<usize> = (0..frame.len())
+                .filter(|&i| i != index && frame.neighbour_vector(index, i).norm() <= cutoff)
+                .collect();
+            assert_eq!(neighbours, expected);
+        }
+    }
+
+    #[test]
+    fn nearest_opposite_faces() {
+        let mut position = vec![[-4.9, 0., 0.], [4.9, 0., 0.]];
+        for x in -3..=3 {
+            for y in -3..=3 {
+                position.push([x as f32, y as f32, 0.]);
+            }
+        }
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [10., 10., 1., 0., 0., 0.]).into();
+        assert_eq!(frame.particle_neighbours_n(0, 1).collect::<Vec<_>>(), [1]);
+        assert_eq!(frame.particle_neighbours_n(1, 1).collect::<Vec<_>>(), [0]);
+        let within: Vec<usize> = frame.neighbours_cutoff(0.5).next().unwrap().collect();
+        assert_eq!(within, [1]);
+    }
+
+    #[test]
+    fn cutoff_boundary_inclusive() {
+        use crate::learning::{extract_features_cutoff, MISSING_NEIGHBOUR};
+        use crate::order::num_neighbours;
+
+        // The second particle is at exactly the cutoff, with the third well beyond it
+        let position = vec![[0.; 3], [1.5, 0., 0.], [0., 4., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [10., 10., 1., 0., 0., 0.]).into();
+
+        let within: Vec<Vec<usize>> = frame
+            .neighbours_cutoff(1.5)
+            .map(|neighs| neighs.collect())
+            .collect();
+        assert_eq!(within, vec![vec![1], vec![0], vec![]]);
+        assert_eq!(num_neighbours(&frame, 1.5), [1, 1, 0]);
+        let features = extract_features_cutoff(&frame, 1.5);
+        assert_eq!(features[0][0], 0.);
+        assert_eq!(features[0][1], MISSING_NEIGHBOUR);
+    }
+
+    #[test]
+    fn property_roundtrip() -> Result<(), Error> {
+        let mut frame = square_lattice(4);
+        let values: Vec<f32> = (0..frame.len()).map(|i| i as f32).collect();
+        frame.set_property("order", values.clone())?;
+        assert_eq!(frame.get_property("order"), Some(values.as_slice()));
+        assert_eq!(frame.get_property("missing"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn property_length_mismatch() {
+        let mut frame = square_lattice(4);
+        assert!(frame.set_property("order", vec![0.; 3]).is_err());
+        assert_eq!(frame.get_property("order"), None);
+    }
 
     #[test]
     fn distance() {
@@ -9,14 +9,48 @@
 
 //! Implement a K-Nearest Neighbours classification algorithm
 
-use crate::learning::Classification;
+use crate::learning::{Classification, TieBreak};
 use anyhow::{anyhow, Error};
 use itertools::izip;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
 use rstar::{Point, PointDistance, RTree, RTreeObject, AABB};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::Path;
 
 type Float = f32;
 
+/// The measure of distance between two sets of features
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+pub enum Metric {
+    /// The squared euclidean distance between the features
+    #[default]
+    Euclidean,
+    /// Each feature is an angle, so the difference in each dimension wraps at $2\pi$
+    Angular,
+}
+
+impl Metric {
+    pub fn distance_2<F: Point<Scalar = Float>>(&self, a: &F, b: &F) -> Float {
+        let mut distance = 0.;
+        for i in 0..F::DIMENSIONS {
+            let d = match self {
+                Metric::Euclidean => a.nth(i) - b.nth(i),
+                Metric::Angular => {
+                    let d = (a.nth(i) - b.nth(i)).rem_euclid(std::f32::consts::TAU);
+                    d.min(std::f32::consts::TAU - d)
+                }
+            };
+            distance += d * d;
+        }
+        distance
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Features<F, L>
 where
@@ -25,6 +59,9 @@ where
 {
     features: F,
     label: L,
+    /// The position of the features within the data used for training
+    #[serde(default)]
+    index: usize,
 }
 
 impl<F, L> Features<F, L>
@@ -33,7 +70,11 @@ where
     L: Classification,
 {
     pub fn new(features: F, label: L) -> Features<F, L> {
-        Features { label, features }
+        Features {
+            label,
+            features,
+            index: 0,
+        }
     }
 }
 
@@ -55,12 +96,7 @@ where
     L: Classification,
 {
     fn distance_2(&self, point: &F) -> F::Scalar {
-        let mut distance = 0.;
-        for i in 0..F::DIMENSIONS {
-            let d = self.features.nth(i) - point.nth(i);
-            distance += d * d;
-        }
-        distance
+        Metric::Euclidean.distance_2(&self.features, point)
     }
 }
 
@@ -72,6 +108,13 @@ where
 {
     tree: Option<RTree<Features<F, L>>>,
     k: usize,
+    #[serde(default)]
+    tie_break: TieBreak,
+    #[serde(default)]
+    metric: Metric,
+    /// The number of dimensions of the features used for training
+    #[serde(default)]
+    dimensions: Option<usize>,
 }
 
 impl<F, L> Default for KNN<F, L>
@@ -80,7 +123,13 @@ where
     L: Classification,
 {
     fn default() -> KNN<F, L> {
-        Self { tree: None, k: 5 }
+        Self {
+            tree: None,
+            k: 5,
+            tie_break: TieBreak::default(),
+            metric: Metric::default(),
+            dimensions: None,
+        }
     }
 }
 
@@ -89,36 +138,210 @@ where
     F: Point<Scalar = Float>,
     L: Classification,
 {
+    /// Set the number of nearest neighbours which vote on the classification
+    pub fn with_k(mut self, k: usize) -> Self {
+        self.k = k;
+        self
+    }
+
+    /// Set the strategy used to resolve ties between the votes of the nearest neighbours
+    pub fn with_tie_break(mut self, tie_break: TieBreak) -> Self {
+        self.tie_break = tie_break;
+        self
+    }
+
+    /// Set the measure of distance used to find the nearest neighbours
+    ///
+    /// The neighbour tree only supports the euclidean metric, so any other metric searches over
+    /// all the training points, which is much slower.
+    ///
+    pub fn with_metric(mut self, metric: Metric) -> Self {
+        self.metric = metric;
+        self
+    }
+
     /// Create an algorithm to classify new features into one of the labels
     ///
     /// Every time this function is run a new algorithm is generated, rather than updating or
-    /// adding points to the existing one.
+    /// adding points to the existing one. There has to be a label for each of the features,
+    /// otherwise an error is returned.
+    ///
+    pub fn fit(&mut self, features: &[F], labels: &[L]) -> Result<(), Error> {
+        if features.len() != labels.len() {
+            return Err(anyhow!(
+                "There are {} features, while there are {} labels",
+                features.len(),
+                labels.len()
+            ));
+        }
+        let values: Vec<Features<F, L>> = izip!(0.., features, labels)
+            .map(|(index, &feat, &class)| Features {
+                index,
+                ..Features::new(feat, class)
+            })
+            .collect();
+
+        self.build_tree(values);
+        Ok(())
+    }
+
+    /// Train the model using a random sample of at most `max_points` of the features
     ///
-    pub fn fit(&mut self, features: &[F], labels: &[L]) {
-        let values: Vec<Features<F, L>> = izip!(features, labels)
-            .map(|(&feat, &class)| Features::new(feat, class))
+    /// The sample preserves the fraction of the features with each label, with the selection
+    /// determined by the `seed`, so fitting the same features with the same seed gives the same
+    /// model. Where there are fewer than `max_points` features, all of them are used.
+    ///
+    pub fn fit_subsampled(
+        &mut self,
+        features: &[F],
+        labels: &[L],
+        max_points: usize,
+        seed: u64,
+    ) -> Result<(), Error> {
+        if features.len() != labels.len() {
+            return Err(anyhow!(
+                "There are {} features, while there are {} labels",
+                features.len(),
+                labels.len()
+            ));
+        }
+        if features.len() <= max_points {
+            return self.fit(features, labels);
+        }
+
+        // The indices of the features with each label, in the order the labels first appear
+        let mut groups: Vec<(L, Vec<usize>)> = Vec::new();
+        for (index, &label) in labels.iter().enumerate() {
+            match groups.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, indices)) => indices.push(index),
+                None => groups.push((label, vec![index])),
+            }
+        }
+
+        // Each label gets a share of the points proportional to the number of features, with the
+        // points lost to rounding down given to the labels in order.
+        let mut quotas: Vec<usize> = groups
+            .iter()
+            .map(|(_, indices)| indices.len() * max_points / features.len())
             .collect();
+        let mut remaining = max_points - quotas.iter().sum::<usize>();
+        for (quota, (_, indices)) in quotas.iter_mut().zip(groups.iter()) {
+            let extra = remaining.min(indices.len() - *quota);
+            *quota += extra;
+            remaining -= extra;
+        }
+
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut selected: Vec<usize> = Vec::with_capacity(max_points);
+        for ((_, indices), quota) in groups.iter_mut().zip(quotas) {
+            indices.shuffle(&mut rng);
+            selected.extend_from_slice(&indices[..quota]);
+        }
+        selected.sort_unstable();
+
+        // The features keep their index within all the features, rather than the sample
+        self.build_tree(
+            selected
+                .into_iter()
+                .map(|index| Features {
+                    index,
+                    ..Features::new(features[index], labels[index])
+                })
+                .collect(),
+        );
+        Ok(())
+    }
 
+    fn build_tree(&mut self, values: Vec<Features<F, L>>) {
         self.tree = Some(RTree::bulk_load(values));
+        self.dimensions = Some(F::DIMENSIONS);
+    }
+
+    /// Save the model to a file, allowing it to be reused without retraining
+    pub fn save<P: AsRef<Path>>(&self, filename: P) -> Result<(), Error>
+    where
+        Self: Serialize,
+    {
+        let writer = BufWriter::new(File::create(filename)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Load a model which was previously saved using [`KNN::save`]
+    pub fn load<P: AsRef<Path>>(filename: P) -> Result<Self, Error>
+    where
+        Self: DeserializeOwned,
+    {
+        let reader = BufReader::new(File::open(filename)?);
+        Ok(serde_json::from_reader(reader)?)
+    }
+
+    /// Whether the model has been trained, either by fitting it or loading a trained model
+    pub fn is_trained(&self) -> bool {
+        self.tree.is_some()
     }
 
+    /// Classify each of the features using the votes of the nearest training points
+    ///
+    /// This returns an error when the model hasn't been trained, or where the features have a
+    /// different number of dimensions to those used for training, like for a model loaded from a
+    /// file created with different features.
+    ///
     pub fn predict(&self, features: &[F]) -> Result<Vec<L>, Error> {
-        if let Some(tree) = &self.tree {
-            // Find the k-Nearest Neighbours
-            Ok(features
-                .iter()
-                .map(|feat| {
-                    let values: Vec<L> = tree
-                        .nearest_neighbor_iter(feat)
-                        .take(self.k)
-                        .map(|x| x.label)
-                        .collect();
-
-                    L::consensus(&values)
-                })
-                .collect())
-        } else {
-            Err(anyhow!("The tree has not yet been initialised"))
+        let tree = self.trained_tree()?;
+        Ok(features
+            .iter()
+            .map(|feat| {
+                let values: Vec<L> = self.nearest(tree, feat).iter().map(|x| x.label).collect();
+                L::consensus(&values, self.tie_break)
+            })
+            .collect())
+    }
+
+    /// Classify each of the features, along with the training points which voted on the class
+    ///
+    /// The training points are given as their index within the features used for training,
+    /// ordered from the nearest to the furthest.
+    ///
+    pub fn predict_with_neighbours(&self, features: &[F]) -> Result<Vec<(L, Vec<usize>)>, Error> {
+        let tree = self.trained_tree()?;
+        Ok(features
+            .iter()
+            .map(|feat| {
+                let nearest = self.nearest(tree, feat);
+                let values: Vec<L> = nearest.iter().map(|x| x.label).collect();
+                let indices = nearest.iter().map(|x| x.index).collect();
+                (L::consensus(&values, self.tie_break), indices)
+            })
+            .collect())
+    }
+
+    /// The tree of training points, checking it is compatible with the features being classified
+    fn trained_tree(&self) -> Result<&RTree<Features<F, L>>, Error> {
+        if let Some(dimensions) = self.dimensions.filter(|&d| d != F::DIMENSIONS) {
+            return Err(anyhow!(
+                "The model was trained with {} dimensional features, while the features have {} dimensions",
+                dimensions,
+                F::DIMENSIONS
+            ));
+        }
+        self.tree
+            .as_ref()
+            .ok_or_else(|| anyhow!("The tree has not yet been initialised"))
+    }
+
+    /// Find the k nearest training points to the features, nearest first
+    fn nearest<'a>(&self, tree: &'a RTree<Features<F, L>>, feat: &F) -> Vec<&'a Features<F, L>> {
+        match self.metric {
+            Metric::Euclidean => tree.nearest_neighbor_iter(feat).take(self.k).collect(),
+            metric => {
+                let mut distances: Vec<(Float, &Features<F, L>)> = tree
+                    .iter()
+                    .map(|x| (metric.distance_2(&x.features, feat), x))
+                    .collect();
+                distances.sort_by(|a, b| a.0.total_cmp(&b.0));
+                distances.into_iter().take(self.k).map(|x| x.1).collect()
+            }
         }
     }
 }
@@ -131,7 +354,7 @@ mod tests {
     #[test]
     fn simple_classification() -> Result<(), Error> {
         let mut knn = KNN::default();
-        knn.fit(&vec![[0.; 2]; 10], &vec![Classes::Liquid; 10]);
+        knn.fit(&vec![[0.; 2]; 10], &vec![Classes::Liquid; 10])?;
         assert_eq!(knn.predict(&vec![[0.; 2]; 5])?, [Classes::Liquid; 5]);
         Ok(())
     }
@@ -143,7 +366,7 @@ mod tests {
         features.extend(&vec![[1.; 2]; 10]);
         let mut classes = vec![Classes::Liquid; 10];
         classes.extend(&vec![Classes::P2; 10]);
-        knn.fit(&features, &classes);
+        knn.fit(&features, &classes)?;
         assert_eq!(knn.predict(&vec![[0.; 2]; 5])?, [Classes::Liquid; 5]);
         assert_eq!(knn.predict(&vec![[1.; 2]; 5])?, [Classes::P2; 5]);
         Ok(())
@@ -158,9 +381,140 @@ mod tests {
         features[10] = [0., 0.];
         let mut classes = vec![Classes::Liquid; 10];
         classes.extend(&vec![Classes::P2; 10]);
-        knn.fit(&features, &classes);
+        knn.fit(&features, &classes)?;
         assert_eq!(knn.predict(&vec![[0.; 2]; 5])?, [Classes::Liquid; 5]);
         assert_eq!(knn.predict(&vec![[1.; 2]; 5])?, [Classes::P2; 5]);
         Ok(())
     }
+
+    #[test]
+    fn fit_length_mismatch() {
+        let mut knn = KNN::default();
+        assert!(knn.fit(&[[0.; 2]; 10], &[Classes::Liquid; 9]).is_err());
+        assert!(!knn.is_trained());
+        assert!(knn.predict(&vec![[0.; 2]; 5]).is_err());
+    }
+
+    #[test]
+    fn predict_dimension_mismatch() -> Result<(), Error> {
+        let mut knn = KNN::default();
+        knn.fit(&[[0.; 2]; 10], &[Classes::Liquid; 10])?;
+        // A model file which records training with a different number of features
+        let contents = serde_json::to_string(&knn)?.replace("\"dimensions\":2", "\"dimensions\":3");
+        let loaded: KNN<[f32; 2], Classes> = serde_json::from_str(&contents)?;
+        assert!(loaded.is_trained());
+        let error = loaded.predict(&[[0.; 2]; 5]).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("trained with 3 dimensional features"));
+        Ok(())
+    }
+
+    #[test]
+    fn fit_subsampled_capped() -> Result<(), Error> {
+        let features: Vec<[f32; 2]> = (0..100).map(|i| [i as f32, (i * 7 % 13) as f32]).collect();
+        // A quarter of the features are labelled P2
+        let classes: Vec<Classes> = (0..100)
+            .map(|i| {
+                if i % 4 == 0 {
+                    Classes::P2
+                } else {
+                    Classes::Liquid
+                }
+            })
+            .collect();
+        let fitted = |max_points: usize, seed: u64| -> Result<Vec<([f32; 2], Classes)>, Error> {
+            let mut knn = KNN::default();
+            knn.fit_subsampled(&features, &classes, max_points, seed)?;
+            let mut points: Vec<([f32; 2], Classes)> = knn
+                .tree
+                .expect("The tree is created by fitting")
+                .iter()
+                .map(|f| (f.features, f.label))
+                .collect();
+            points.sort_by(|a, b| a.0[0].total_cmp(&b.0[0]));
+            Ok(points)
+        };
+
+        let points = fitted(20, 1)?;
+        assert_eq!(points.len(), 20);
+        let p2 = points.iter().filter(|(_, l)| *l == Classes::P2).count();
+        assert_eq!(p2, 5);
+        assert_eq!(points, fitted(20, 1)?);
+        assert_ne!(points, fitted(20, 2)?);
+        // With fewer features than the maximum, all the features are used
+        assert_eq!(fitted(200, 1)?.len(), 100);
+
+        // The neighbours are the indices of all the features, not just those in the sample
+        let mut knn = KNN::default().with_k(1);
+        knn.fit_subsampled(&features, &classes, 20, 1)?;
+        for (point, _) in points.iter() {
+            let (_, indices) = knn.predict_with_neighbours(&[*point])?.remove(0);
+            assert_eq!(features[indices[0]], *point);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn predict_neighbour_indices() -> Result<(), Error> {
+        let features: Vec<[f32; 2]> = (0..10).map(|i| [i as f32, 0.]).collect();
+        let classes: Vec<Classes> = (0..10)
+            .map(|i| if i < 5 { Classes::Liquid } else { Classes::P2 })
+            .collect();
+        let mut knn = KNN::default().with_k(3);
+        knn.fit(&features, &classes)?;
+
+        let predictions = knn.predict_with_neighbours(&[[6.9, 0.], [-1., 0.]])?;
+        assert_eq!(predictions[0], (Classes::P2, vec![7, 6, 8]));
+        assert_eq!(predictions[1], (Classes::Liquid, vec![0, 1, 2]));
+        let labels: Vec<Classes> = predictions.into_iter().map(|(l, _)| l).collect();
+        assert_eq!(labels, knn.predict(&[[6.9, 0.], [-1., 0.]])?);
+        Ok(())
+    }
+
+    #[test]
+    fn angular_metric_wraps() {
+        let tau = std::f32::consts::TAU;
+        let a = [0.1, 0.1];
+        let b = [tau - 0.1, tau - 0.1];
+        assert!(Metric::Angular.distance_2(&a, &b) < 0.1);
+        assert!(Metric::Euclidean.distance_2(&a, &b) > 30.);
+    }
+
+    #[test]
+    fn angular_classification() -> Result<(), Error> {
+        let mut features = vec![[0.05; 2]; 10];
+        features.extend(&vec![[3.; 2]; 10]);
+        let mut classes = vec![Classes::Liquid; 10];
+        classes.extend(&vec![Classes::P2; 10]);
+        let query = vec![[std::f32::consts::TAU - 0.05; 2]; 2];
+
+        let mut knn = KNN::default();
+        knn.fit(&features, &classes)?;
+        assert_eq!(knn.predict(&query)?, [Classes::P2; 2]);
+
+        let mut knn = KNN::default().with_metric(Metric::Angular);
+        knn.fit(&features, &classes)?;
+        assert_eq!(knn.predict(&query)?, [Classes::Liquid; 2]);
+        Ok(())
+    }
+
+    #[test]
+    fn save_load() -> Result<(), Error> {
+        let mut knn = KNN::default();
+        let mut features = vec![[0.; 2]; 10];
+        features.extend(&vec![[1.; 2]; 10]);
+        let mut classes = vec![Classes::Liquid; 10];
+        classes.extend(&vec![Classes::P2; 10]);
+        knn.fit(&features, &classes)?;
+
+        let mut filename = std::env::temp_dir();
+        filename.push(format!("knn-save-load-{}.json", std::process::id()));
+        knn.save(&filename)?;
+        let loaded: KNN<[f32; 2], Classes> = KNN::load(&filename)?;
+        std::fs::remove_file(&filename)?;
+
+        assert_eq!(loaded.predict(&features)?, knn.predict(&features)?);
+        Ok(())
+    }
 }
@@ -4,13 +4,25 @@
 // Distributed under terms of the MIT license.
 //
 
+use crate::analysis::AnalysisConfig;
 use crate::frame::Frame;
 use crate::knn::KNN;
-use anyhow::Error;
+use crate::order::{self, hexatic_order, local_density, orientational_order};
+use crate::steinhardt::steinhardt_order;
+use anyhow::{bail, Error};
 use gsd::GSDTrajectory;
+use itertools::izip;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::str::FromStr;
 
+/// Create the features of each particle from the orientations of its 6 nearest neighbours
+///
+/// Each feature is the angle between the orientation of the particle and a neighbour, ordered by
+/// the distance to the neighbour. Where the frame has too few particles for 6 neighbours, the
+/// features of the missing neighbours are 0, the same as the features the existing models were
+/// trained with.
+///
 pub fn extract_features(frame: &Frame) -> Vec<[f32; 6]> {
     frame
         .neighbours_n(6)
@@ -25,11 +37,112 @@ pub fn extract_features(frame: &Frame) -> Vec<[f32; 6]> {
         .collect()
 }
 
-pub trait Classification: std::fmt::Debug + Clone + Copy + FromStr + PartialEq + Eq {
-    fn consensus(votes: &[Self]) -> Self;
+/// The value of a feature where there is no neighbour within the cutoff
+pub const MISSING_NEIGHBOUR: f32 = -1.;
+
+/// Create the features of each particle from the neighbours within a distance of `cutoff`
+///
+/// This is the same as [`extract_features`], except only the neighbours within a distance of
+/// `cutoff` are used, which like [`Frame::neighbours_cutoff`] includes the cutoff itself, with
+/// the features of any missing neighbours set to [`MISSING_NEIGHBOUR`]. Since the angle between
+/// two orientations is in the range $[0, \pi]$, each missing neighbour places the particle at
+/// least a distance of 1 away from the particles with a neighbour in that position, so the KNN
+/// groups the particles with the same number of neighbours.
+///
+pub fn extract_features_cutoff(frame: &Frame, cutoff: f32) -> Vec<[f32; 6]> {
+    (0..frame.len())
+        .map(|index| {
+            let mut features = [MISSING_NEIGHBOUR; 6];
+            let neighbours = frame
+                .particle_neighbours_n(index, features.len())
+                .take_while(|&n| {
+                    frame.neighbour_vector(index, n).norm_squared() <= cutoff * cutoff
+                });
+            for (feature, neighbour) in features.iter_mut().zip(neighbours) {
+                *feature = frame.orientation[index].angle_to(&frame.orientation[neighbour]);
+            }
+            features
+        })
+        .collect()
+}
+
+/// Create the features of each particle, preceded by the orientation of the particle itself
+///
+/// The features are `[self_angle, n1, ..., n6]`, where the neighbour features are the same as
+/// [`extract_features`] and `self_angle` is the angle of rotation of the particle from the lab
+/// frame. Unlike the neighbour features this isn't invariant to rotating the whole frame, so it is
+/// only useful where the absolute orientation of the particles is meaningful.
+///
+pub fn extract_features_with_self(frame: &Frame) -> Vec<[f32; 7]> {
+    extract_features(frame)
+        .into_iter()
+        .zip(frame.orientation.iter())
+        .map(|(neighbours, orientation)| {
+            let mut features = [0.; 7];
+            features[0] = orientation.angle();
+            features[1..].copy_from_slice(&neighbours);
+            features
+        })
+        .collect()
+}
+
+/// Create the features of each particle from a combination of order parameters
+///
+/// The features are `[orient_order, hexatic_order, local_density, coordination]`, with the first
+/// three using the `num_neighbours` nearest particles, while the coordination is the number of
+/// particles within `cutoff`. Unlike [`extract_features`], each of these has a physical meaning.
+///
+pub fn combined_features(frame: &Frame, num_neighbours: usize, cutoff: f32) -> Vec<[f32; 4]> {
+    izip!(
+        orientational_order(frame, num_neighbours),
+        hexatic_order(frame, num_neighbours),
+        local_density(frame, num_neighbours),
+        order::num_neighbours(frame, cutoff),
+    )
+    .map(|(orient, hexatic, density, coordination)| [orient, hexatic, density, coordination as f32])
+    .collect()
+}
+
+/// Create a rotationally invariant feature vector for each particle from Steinhardt order parameters
+///
+/// The features of each particle are the values of $q_l$ for each of the `ls`, using the bonds
+/// to the `num_neighbours` nearest particles. Unlike [`extract_features`] these describe the
+/// arrangement of the neighbours in three dimensions, which distinguishes crystal structures.
+///
+pub fn extract_steinhardt_features(
+    frame: &Frame,
+    ls: &[usize],
+    num_neighbours: usize,
+) -> Vec<Vec<f32>> {
+    let orders: Vec<Vec<f32>> = ls
+        .iter()
+        .map(|&l| steinhardt_order(frame, l, num_neighbours))
+        .collect();
+    (0..frame.len())
+        .map(|index| orders.iter().map(|order| order[index]).collect())
+        .collect()
+}
+
+/// The strategy used to resolve a tie in the number of votes for each class
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TieBreak {
+    /// Choose the tied class with the vote nearest to the point being classified
+    #[default]
+    Nearest,
+    /// Choose the tied class which appears first in the definition of the classes
+    First,
+}
+
+pub trait Classification: std::fmt::Debug + Clone + Copy + PartialEq + Eq {
+    /// Find the class with the most votes
+    ///
+    /// The votes are ordered by their distance from the point being classified, nearest first,
+    /// which is the information used to resolve ties with the [`TieBreak::Nearest`] strategy.
+    ///
+    fn consensus(votes: &[Self], tie_break: TieBreak) -> Self;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum Classes {
     Liquid,
     P2,
@@ -37,6 +150,27 @@ pub enum Classes {
     PG,
 }
 
+impl Classes {
+    fn index(self) -> usize {
+        match self {
+            Self::Liquid => 0,
+            Self::P2 => 1,
+            Self::P2GG => 2,
+            Self::PG => 3,
+        }
+    }
+
+    fn from_index(index: usize) -> Self {
+        match index {
+            0 => Self::Liquid,
+            1 => Self::P2,
+            2 => Self::P2GG,
+            3 => Self::PG,
+            _ => unreachable!("Assigning values to a class which doesn't exist"),
+        }
+    }
+}
+
 impl FromStr for Classes {
     type Err = Error;
 
@@ -50,39 +184,142 @@ impl FromStr for Classes {
     }
 }
 
-impl Classification for Classes {
-    fn consensus(votes: &[Self]) -> Self {
-        let mut boxes = [0_usize; 4];
-        for vote in votes {
-            match vote {
-                Self::Liquid => boxes[0] += 1,
-                Self::P2 => boxes[1] += 1,
-                Self::P2GG => boxes[2] += 1,
-                Self::PG => boxes[3] += 1,
-            }
-        }
-        let max_index = boxes
+/// Find the class with the most votes, where each class is identified by its index
+///
+/// This implements [`Classification::consensus`] for any set of classes which can be converted
+/// to and from an index, with the order of the indices used by [`TieBreak::First`]. Where there
+/// are no votes, this falls back to the class with index 0.
+///
+fn majority_vote<L: Copy>(
+    votes: &[L],
+    tie_break: TieBreak,
+    index: impl Fn(L) -> usize,
+    from_index: impl Fn(usize) -> L,
+) -> L {
+    let num_classes = votes.iter().map(|&v| index(v) + 1).max().unwrap_or(1);
+    let mut boxes = vec![0_usize; num_classes];
+    for &vote in votes {
+        boxes[index(vote)] += 1;
+    }
+    let max_votes = boxes.iter().copied().max().unwrap_or(0);
+
+    match tie_break {
+        TieBreak::Nearest => votes
             .iter()
-            .enumerate()
-            .max_by_key(|&(_, item)| item)
-            .unwrap_or((0, &0))
-            .0;
+            .find(|&&v| boxes[index(v)] == max_votes)
+            .copied()
+            .unwrap_or_else(|| from_index(0)),
+        TieBreak::First => from_index(
+            boxes
+                .iter()
+                .position(|&count| count == max_votes)
+                .unwrap_or(0),
+        ),
+    }
+}
 
-        match max_index {
-            0 => Self::Liquid,
-            1 => Self::P2,
-            2 => Self::P2GG,
-            3 => Self::PG,
-            _ => unreachable!("Assigning values to a class which doesn't exist"),
+impl Classification for Classes {
+    fn consensus(votes: &[Self], tie_break: TieBreak) -> Self {
+        majority_vote(votes, tie_break, Self::index, Self::from_index)
+    }
+}
+
+/// A class defined at runtime, which is the index of the class within a [`ClassDefinitions`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct StringClass(pub usize);
+
+impl Classification for StringClass {
+    fn consensus(votes: &[Self], tie_break: TieBreak) -> Self {
+        majority_vote(votes, tie_break, |c| c.0, StringClass)
+    }
+}
+
+/// The names of a set of classes, along with the pattern identifying the files of each class
+///
+/// This allows for classes other than the polymorphs of [`Classes`], with the classes and their
+/// order chosen at runtime. Each class is a [`StringClass`] containing its index, so the order of
+/// the definitions is the order used by [`TieBreak::First`]. The first class takes the place of
+/// [`Classes::Liquid`], being the class of the particles surrounding the crystal when training
+/// with [`run_training_with_classes`].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClassDefinitions {
+    definitions: Vec<(String, String)>,
+}
+
+impl ClassDefinitions {
+    /// Create the classes from pairs of the name and the pattern of each class
+    pub fn new(definitions: Vec<(String, String)>) -> Self {
+        ClassDefinitions { definitions }
+    }
+
+    /// The class of the first definition with a pattern contained in `s`
+    ///
+    /// This is the equivalent of parsing the filename as [`Classes`], so a pattern which contains
+    /// another pattern needs to be defined first. An empty pattern matches everything, so is
+    /// useful as the final definition.
+    ///
+    pub fn classify(&self, s: &str) -> Result<StringClass, Error> {
+        match self
+            .definitions
+            .iter()
+            .position(|(_, pattern)| s.contains(pattern.as_str()))
+        {
+            Some(index) => Ok(StringClass(index)),
+            None => bail!("'{}' doesn't match any of the classes", s),
         }
     }
+
+    /// The name of a class
+    pub fn name(&self, class: StringClass) -> Option<&str> {
+        self.definitions.get(class.0).map(|(name, _)| name.as_str())
+    }
+
+    pub fn len(&self) -> usize {
+        self.definitions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.definitions.is_empty()
+    }
 }
 
-fn classify_file(filename: &str, index: usize) -> Result<Vec<([f32; 6], Classes)>, Error> {
-    let crystal = Classes::from_str(filename)?;
-    let frame: Frame = GSDTrajectory::new(&filename)?
-        .get_frame(index as u64)?
-        .into();
+/// The fraction of the particles assigned to each of the classes
+///
+/// Every class is present in the result, with a fraction of 0 for the classes without any
+/// particles, so the fractions sum to 1. When there are no particles, the fraction of every
+/// class is NaN.
+///
+pub fn class_fractions(classes: &[Classes]) -> HashMap<Classes, f32> {
+    let mut counts = [0_usize; 4];
+    for class in classes {
+        counts[class.index()] += 1;
+    }
+    counts
+        .iter()
+        .enumerate()
+        .map(|(index, &count)| {
+            (
+                Classes::from_index(index),
+                count as f32 / classes.len() as f32,
+            )
+        })
+        .collect()
+}
+
+/// Label the features of the particles in a training file
+///
+/// The particles in the centre of the frame are labelled `crystal`, while those surrounding the
+/// crystal are labelled `liquid`.
+///
+fn classify_file<L: Classification>(
+    filename: &str,
+    index: usize,
+    config: &AnalysisConfig,
+    crystal: L,
+    liquid: L,
+) -> Result<Vec<([f32; 6], L)>, Error> {
+    let frame = Frame::try_new(GSDTrajectory::new(&filename)?.get_frame(index as u64)?)?;
     // Initialise class to be zero for all particles
     Ok(frame
         .position
@@ -94,29 +331,108 @@ fn classify_file(filename: &str, index: usize) -> Result<Vec<([f32; 6], Classes)
                 position[1] / frame.simulation_cell[1],
             ) {
                 // The central region is crystalline
-                (x, y) if x.abs() < 0.28 && y.abs() < 0.28 => Some((feat, crystal)),
+                (x, y) if x.abs() < config.crystal_region && y.abs() < config.crystal_region => {
+                    Some((feat, crystal))
+                }
                 // The surrounding region is interface, so ignore
-                (x, y) if x.abs() < 0.32 && y.abs() < 0.32 => None,
-                _ => Some((feat, Classes::Liquid)),
+                (x, y) if x.abs() < config.liquid_region && y.abs() < config.liquid_region => None,
+                _ => Some((feat, liquid)),
             }
         })
         .collect())
 }
 
 pub fn run_training(filenames: Vec<String>, index: usize) -> Result<KNN<[f32; 6], Classes>, Error> {
-    let mut knn = KNN::default();
+    run_training_with_config(filenames, index, &AnalysisConfig::default())
+}
+
+/// Train the classifier using the regions and number of neighbours from the configuration
+pub fn run_training_with_config(
+    filenames: Vec<String>,
+    index: usize,
+    config: &AnalysisConfig,
+) -> Result<KNN<[f32; 6], Classes>, Error> {
+    train_classifier(filenames, index, config, Classes::from_str, Classes::Liquid)
+}
+
+/// Train the classifier with classes defined at runtime
+///
+/// This is the same as [`run_training_with_config`], with the class of the crystal in each file
+/// found using [`ClassDefinitions::classify`], and the particles surrounding the crystal being
+/// the first of the `definitions`. Files which don't match any of the classes are skipped.
+///
+pub fn run_training_with_classes(
+    filenames: Vec<String>,
+    index: usize,
+    config: &AnalysisConfig,
+    definitions: &ClassDefinitions,
+) -> Result<KNN<[f32; 6], StringClass>, Error> {
+    if definitions.is_empty() {
+        bail!("There are no classes to train the classifier with");
+    }
+    train_classifier(
+        filenames,
+        index,
+        config,
+        |filename| definitions.classify(filename),
+        StringClass(0),
+    )
+}
+
+/// Train a classifier where the class of the crystal in each file is found from the filename
+fn train_classifier<L: Classification>(
+    filenames: Vec<String>,
+    index: usize,
+    config: &AnalysisConfig,
+    crystal: impl Fn(&str) -> Result<L, Error>,
+    liquid: L,
+) -> Result<KNN<[f32; 6], L>, Error> {
+    let mut knn = KNN::default().with_k(config.knn_k);
     let (features, classes): (Vec<_>, Vec<_>) = filenames
         .iter()
-        .filter_map(|f| classify_file(f, index).ok())
+        .filter_map(|f| classify_file(f, index, config, crystal(f).ok()?, liquid).ok())
         .flat_map(|i| i.into_iter())
         .unzip();
-    knn.fit(&features, &classes);
+    if features.is_empty() {
+        bail!(
+            "Unable to find any training data in the files {:?}",
+            filenames
+        );
+    }
+    knn.fit(&features, &classes)?;
     Ok(knn)
 }
 
+/// Classify features using the majority vote of a collection of models
+///
+/// Each model predicts the class of every point, with the predictions for each point combined
+/// using [`Classification::consensus`]. A tie is resolved in favour of the model which appears
+/// first in `models`.
+///
+pub fn ensemble_predict(
+    models: &[KNN<[f32; 6], Classes>],
+    features: &[[f32; 6]],
+) -> Result<Vec<Classes>, Error> {
+    if models.is_empty() {
+        bail!("There are no models to make a prediction");
+    }
+    let predictions = models
+        .iter()
+        .map(|model| model.predict(features))
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok((0..features.len())
+        .map(|index| {
+            let votes: Vec<Classes> = predictions.iter().map(|p| p[index]).collect();
+            Classes::consensus(&votes, TieBreak::Nearest)
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::crystal;
+    use gsd::GSDFrame;
 
     #[test]
     fn create_p2() {
@@ -136,6 +452,291 @@ mod tests {
         assert_eq!(class, Classes::PG);
     }
 
+    #[test]
+    fn consensus_majority() {
+        let votes = [Classes::P2, Classes::Liquid, Classes::PG, Classes::PG];
+        assert_eq!(Classes::consensus(&votes, TieBreak::Nearest), Classes::PG);
+        assert_eq!(Classes::consensus(&votes, TieBreak::First), Classes::PG);
+    }
+
+    #[test]
+    fn consensus_tie_nearest() {
+        let votes = [
+            Classes::P2,
+            Classes::PG,
+            Classes::P2,
+            Classes::PG,
+            Classes::PG,
+            Classes::P2,
+        ];
+        for _ in 0..10 {
+            assert_eq!(Classes::consensus(&votes, TieBreak::Nearest), Classes::P2);
+        }
+    }
+
+    #[test]
+    fn consensus_tie_first() {
+        let votes = [
+            Classes::PG,
+            Classes::Liquid,
+            Classes::PG,
+            Classes::Liquid,
+            Classes::PG,
+            Classes::Liquid,
+        ];
+        for _ in 0..10 {
+            assert_eq!(Classes::consensus(&votes, TieBreak::First), Classes::Liquid);
+        }
+    }
+
+    #[test]
+    fn custom_classes() -> Result<(), Error> {
+        let definitions = ClassDefinitions::new(vec![
+            ("oblique".to_string(), "-obl".to_string()),
+            ("square".to_string(), "-sq".to_string()),
+            ("fluid".to_string(), "".to_string()),
+        ]);
+        let files = ["state-obl.gsd", "state-sq.gsd", "state.gsd"];
+        let centres = [[0., 0.], [5., 0.], [0., 5.]];
+
+        let mut features = Vec::new();
+        let mut labels = Vec::new();
+        for (file, centre) in files.iter().zip(centres.iter()) {
+            let class = definitions.classify(file)?;
+            for offset in [[0., 0.], [0.1, 0.], [0., 0.1], [-0.1, 0.]].iter() {
+                features.push([centre[0] + offset[0], centre[1] + offset[1]]);
+                labels.push(class);
+            }
+        }
+        let mut knn: KNN<[f32; 2], StringClass> = KNN::default();
+        knn.fit(&features, &labels)?;
+
+        let predicted = knn.predict(&[[5.1, 0.1], [0.1, 4.9], [-0.1, 0.1]])?;
+        let names: Vec<&str> = predicted
+            .into_iter()
+            .filter_map(|class| definitions.name(class))
+            .collect();
+        assert_eq!(names, ["square", "fluid", "oblique"]);
+        Ok(())
+    }
+
+    #[test]
+    fn custom_classes_training() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let config = AnalysisConfig::default();
+        let definitions = ClassDefinitions::new(vec![
+            ("fluid".to_string(), "-fluid".to_string()),
+            ("crystal".to_string(), "trajectory".to_string()),
+        ]);
+        let knn = run_training_with_classes(vec![filename.to_string()], 1, &config, &definitions)?;
+
+        let frame = Frame::try_new(GSDTrajectory::new(filename)?.get_frame(1)?)?;
+        let predicted = knn.predict(&extract_features(&frame))?;
+        assert_eq!(predicted.len(), frame.len());
+        assert!(predicted
+            .iter()
+            .all(|&class| definitions.name(class).is_some()));
+
+        // Without a class for the file there is nothing to train with
+        let unmatched = ClassDefinitions::new(vec![("fluid".to_string(), "-fluid".to_string())]);
+        assert!(
+            run_training_with_classes(vec![filename.to_string()], 1, &config, &unmatched).is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn custom_classes_no_match() {
+        let definitions = ClassDefinitions::new(vec![("square".to_string(), "-sq".to_string())]);
+        assert!(definitions.classify("state.gsd").is_err());
+        assert_eq!(definitions.len(), 1);
+    }
+
+    #[test]
+    fn class_fractions_mixture() {
+        let classes = [
+            Classes::Liquid,
+            Classes::P2,
+            Classes::Liquid,
+            Classes::Liquid,
+        ];
+        let fractions = class_fractions(&classes);
+        assert_eq!(fractions.len(), 4);
+        assert_eq!(fractions[&Classes::Liquid], 0.75);
+        assert_eq!(fractions[&Classes::P2], 0.25);
+        assert_eq!(fractions[&Classes::P2GG], 0.);
+        assert_eq!(fractions[&Classes::PG], 0.);
+    }
+
+    #[test]
+    fn class_fractions_empty() {
+        let fractions = class_fractions(&[]);
+        assert_eq!(fractions.len(), 4);
+        assert!(fractions.values().all(|f| f.is_nan()));
+    }
+
+    #[test]
+    fn consensus_empty() {
+        assert_eq!(Classes::consensus(&[], TieBreak::Nearest), Classes::Liquid);
+        assert_eq!(Classes::consensus(&[], TieBreak::First), Classes::Liquid);
+    }
+
+    #[test]
+    fn ensemble_blind_spots() -> Result<(), Error> {
+        let classes = [Classes::Liquid, Classes::P2, Classes::PG];
+        // Each model is missing the training data for one of the classes
+        let models = (0..3)
+            .map(|missing| {
+                let (features, labels): (Vec<_>, Vec<_>) = (0..3)
+                    .filter(|&c| c != missing)
+                    .flat_map(|c| vec![([c as f32; 6], classes[c]); 5])
+                    .unzip();
+                let mut knn = KNN::default();
+                knn.fit(&features, &labels)?;
+                Ok(knn)
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        let features = [[0.; 6], [1.; 6], [2.; 6]];
+        for (missing, model) in models.iter().enumerate() {
+            assert_ne!(model.predict(&features)?[missing], classes[missing]);
+        }
+        assert_eq!(ensemble_predict(&models, &features)?, classes);
+        assert!(ensemble_predict(&[], &features).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn steinhardt_features_separate_crystals() {
+        // A small displacement of each particle removes the ties in the distances to neighbours
+        let fcc = crystal(
+            &[[0., 0., 0.], [0.5, 0.5, 0.], [0.5, 0., 0.5], [0., 0.5, 0.5]],
+            [1., 1., 1.],
+            [4, 4, 4],
+            0.01,
+        );
+        let bcc = crystal(
+            &[[0., 0., 0.], [0.5, 0.5, 0.5]],
+            [1., 1., 1.],
+            [5, 5, 5],
+            0.01,
+        );
+        let ls = [4, 6];
+        // The neighbours are the first coordination shell of each crystal
+        let fcc_features = extract_steinhardt_features(&fcc, &ls, 12);
+        let bcc_features = extract_steinhardt_features(&bcc, &ls, 8);
+        assert_eq!(fcc_features.len(), fcc.len());
+        assert!(fcc_features.iter().all(|f| f.len() == ls.len()));
+
+        // Every particle is closer to all the particles of the same crystal than the other crystal
+        let distance = |a: &[f32], b: &[f32]| {
+            a.iter()
+                .zip(b)
+                .map(|(x, y)| (x - y).powi(2))
+                .sum::<f32>()
+                .sqrt()
+        };
+        let spread = |features: &[Vec<f32>]| {
+            features
+                .iter()
+                .map(|f| distance(f, &features[0]))
+                .fold(0., f32::max)
+        };
+        let separation = fcc_features
+            .iter()
+            .flat_map(|f| bcc_features.iter().map(move |b| distance(f, b)))
+            .fold(f32::MAX, f32::min);
+        assert!(separation > 0.2, "{}", separation);
+        assert!(spread(&fcc_features) < separation / 4.);
+        assert!(spread(&bcc_features) < separation / 4.);
+    }
+
+    #[test]
+    fn combined_features_columns() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let frame: Frame = GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        let features = combined_features(&frame, 6, 1.5);
+        assert_eq!(features.len(), frame.len());
+
+        let column = |i: usize| -> Vec<f32> { features.iter().map(|f| f[i]).collect() };
+        assert_eq!(column(0), orientational_order(&frame, 6));
+        assert_eq!(column(1), hexatic_order(&frame, 6));
+        assert_eq!(column(2), local_density(&frame, 6));
+        let coordination: Vec<f32> = order::num_neighbours(&frame, 1.5)
+            .into_iter()
+            .map(|c| c as f32)
+            .collect();
+        assert_eq!(column(3), coordination);
+        Ok(())
+    }
+
+    #[test]
+    fn features_with_self_angle() {
+        let position = vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.]];
+        // A rotation of 90 degrees around the z axis for the second particle
+        let (s, c) = (std::f32::consts::FRAC_PI_4).sin_cos();
+        let orientation = vec![[1., 0., 0., 0.], [c, 0., 0., s], [1., 0., 0., 0.]];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [20., 20., 1., 0., 0., 0.]).into();
+        let features = extract_features_with_self(&frame);
+        let neighbours = extract_features(&frame);
+        assert_eq!(features[0].len(), neighbours[0].len() + 1);
+        assert!(features[0][0].abs() < 1e-6);
+        assert!((features[1][0] - std::f32::consts::FRAC_PI_2).abs() < 1e-6);
+        for (f, n) in features.iter().zip(neighbours.iter()) {
+            assert_eq!(&f[1..], n);
+        }
+    }
+
+    #[test]
+    fn features_too_few_particles() {
+        // Each particle has only 3 neighbours, with the features of the missing neighbours being 0
+        let position = vec![[0.; 3], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]];
+        let orientation: Vec<[f32; 4]> = (0..4)
+            .map(|i| {
+                let half_angle = 0.2 * (i + 1) as f32;
+                [half_angle.cos(), 0., 0., half_angle.sin()]
+            })
+            .collect();
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [4., 4., 1., 0., 0., 0.]).into();
+        for features in extract_features(&frame) {
+            assert!(features[..3].iter().all(|&f| f > 0.));
+            assert_eq!(features[3..], [0.; 3]);
+        }
+    }
+
+    #[test]
+    fn features_cutoff_padding() {
+        let position = vec![[0., 0., 0.], [1., 0., 0.], [5., 5., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; 3];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [20., 20., 1., 0., 0., 0.]).into();
+        let features = extract_features_cutoff(&frame, 1.5);
+        let missing = |f: &[f32; 6]| f.iter().filter(|&&v| v == MISSING_NEIGHBOUR).count();
+        assert_eq!(missing(&features[0]), 5);
+        assert_eq!(missing(&features[1]), 5);
+        assert_eq!(missing(&features[2]), 6);
+    }
+
+    #[test]
+    fn features_cutoff_dense() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let frame: Frame = GSDTrajectory::new(filename)?.get_frame(1)?.into();
+        // With a large cutoff every particle has all its neighbours
+        assert_eq!(
+            extract_features_cutoff(&frame, 10.),
+            extract_features(&frame)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn training_without_data() {
+        assert!(run_training(vec![], 100).is_err());
+        assert!(run_training(vec![String::from("missing-p2.gsd")], 100).is_err());
+    }
+
     #[test]
     fn it_works() {}
 }
@@ -4,12 +4,25 @@
 // Distributed under terms of the MIT license.
 //
 
+pub mod analysis;
+pub mod cell;
 pub mod distance;
+pub mod dynamics;
+pub mod energy;
 pub mod frame;
 pub mod knn;
 pub mod learning;
+pub mod metric;
 pub mod order;
+pub mod results;
+pub mod stats;
+pub mod steinhardt;
+pub mod strain;
+pub mod structure;
 pub mod voronoi;
 
+#[cfg(test)]
+mod test_utils;
+
 pub use distance::*;
 pub use order::*;
@@ -6,66 +6,40 @@
 
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Instant;
 
-use anyhow::Error;
-use clap::Parser;
-use itertools::izip;
-use serde::Serialize;
+use anyhow::{bail, Context, Error};
+use clap::{Args, Parser, Subcommand};
 
-use gsd::GSDTrajectory;
+use gsd::{decimate, GSDTrajectory};
+use trajedy::analysis::{
+    analyse_frame_metrics, feature_rows, AnalysisConfig, CalcResult, FeatureRow, Row, RowFormat,
+    Summary, Timing,
+};
 use trajedy::frame::Frame;
-use trajedy::learning::{extract_features, run_training, Classes};
-use trajedy::voronoi::voronoi_area;
-use trajedy::{hexatic_order, orientational_order};
-
-#[derive(Serialize)]
-struct Row {
-    molecule: usize,
-    timestep: usize,
-    orient_order: f32,
-    hexatic_order: f32,
-    class: Classes,
-    area: Option<f64>,
-}
+use trajedy::knn::KNN;
+use trajedy::learning::{extract_features, run_training_with_config, Classes};
+use trajedy::metric::{PerParticleMetric, SteinhardtOrder};
 
-struct CalcResult {
-    timestep: usize,
-    orient_order: Vec<f32>,
-    hexatic_order: Vec<f32>,
-    class: Vec<Classes>,
-    area: Option<Vec<f64>>,
+#[derive(Parser, Debug, Clone)]
+#[clap(author, version, about, long_about=None)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
 }
 
-#[allow(clippy::from_over_into)]
-impl Into<Vec<Row>> for CalcResult {
-    fn into(self) -> Vec<Row> {
-        let unwrapped_area: Box<dyn Iterator<Item = Option<f64>>> = match self.area {
-            Some(a) => Box::new(a.into_iter().map(Some)),
-            None => Box::new((0..).map(|_| None)),
-        };
-        let timestep = self.timestep as usize;
-        izip!(
-            0..,
-            self.orient_order.into_iter(),
-            self.hexatic_order.into_iter(),
-            self.class.into_iter(),
-            unwrapped_area,
-        )
-        .map(|(molecule, orient_order, hexatic_order, class, area)| Row {
-            molecule,
-            timestep,
-            orient_order,
-            hexatic_order,
-            class,
-            area,
-        })
-        .collect()
-    }
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Compute the order parameters and classification of each particle in a trajectory
+    Analyse(AnalyseArgs),
+    /// Train the machine learning model from labelled configurations, saving it to a file
+    Train(TrainArgs),
+    /// Write a subset of the frames in a trajectory to a new gsd file
+    Convert(ConvertArgs),
 }
 
-#[derive(Parser, Debug, Clone)]
-#[clap(author, version, about, long_about=None)]
-struct Args {
+#[derive(Args, Debug, Clone)]
+struct AnalyseArgs {
     /// The gsd file to process
     #[clap()]
     filename: String,
@@ -84,21 +58,196 @@ struct Args {
     #[clap(long, default_value = "1")]
     skip_frames: usize,
 
-    /// The files which are going to be used for training the machine learning model
+    /// The files which are going to be used for training the machine learning model. Without any
+    /// training files every particle is classified as liquid.
     #[clap(long)]
     training: Vec<String>,
 
+    /// The index of the frame within each training file which is used for training
+    #[clap(long, default_value = "100")]
+    training_index: usize,
+
+    /// A file containing a trained model. Where the file exists the model is loaded from it rather
+    /// than training, otherwise the model trained from the training files is saved to it.
+    #[clap(long, parse(from_os_str))]
+    model: Option<PathBuf>,
+
     /// Whether to compute the voronoi diagram
     #[clap(long)]
     voronoi: bool,
+
+    /// The number of neighbours used to compute the order parameters. The features used for the
+    /// classification always use the 6 nearest neighbours, so this can be changed without
+    /// retraining the model.
+    #[clap(long, default_value = "6")]
+    num_neighbours: usize,
+
+    /// Only classify each particle, skipping the computation of the order parameters. The output
+    /// only contains the molecule, timestep and class columns.
+    #[clap(long, conflicts_with_all = &["voronoi", "steinhardt", "columns"])]
+    classify_only: bool,
+
+    /// Output a single row for each frame with the values averaged over all particles
+    #[clap(long)]
+    summary: bool,
+
+    /// Append the rows to the output file rather than overwriting it, only writing the header
+    /// when the file is empty. It is an error when the columns don't match the header already in
+    /// the file.
+    #[clap(long)]
+    append: bool,
+
+    /// The maximum number of frames which are being analysed or waiting to be written at any
+    /// time, limiting the memory used when writing the output is slower than the analysis.
+    #[clap(long, default_value = "64")]
+    buffer: usize,
+
+    /// Write the features used for the classification of each particle to this csv file
+    #[clap(long, parse(from_os_str))]
+    dump_features: Option<PathBuf>,
+
+    /// Round the floating point values of each particle to this number of decimal places
+    #[clap(long)]
+    precision: Option<usize>,
+
+    /// A comma separated list of the columns to write for each particle, in the order they are
+    /// written. By default all the columns are written.
+    #[clap(long, value_delimiter = ',')]
+    columns: Vec<String>,
+
+    /// Compute the Steinhardt bond order of this degree for each particle, written to the column
+    /// q{l}. This can be given multiple times for different degrees.
+    #[clap(long)]
+    steinhardt: Vec<usize>,
+
+    /// Print the time taken by each stage of the analysis of every frame, followed by a summary
+    #[clap(long)]
+    timing: bool,
+}
+
+#[derive(Args, Debug, Clone)]
+struct TrainArgs {
+    /// The labelled gsd files used for training the model, with the label taken from the filename
+    #[clap(required = true)]
+    training: Vec<String>,
+
+    /// File to save the trained model to
+    #[clap(short, long, parse(from_os_str))]
+    output: PathBuf,
+
+    /// The index of the frame within each file which is used for training
+    #[clap(long, default_value = "100")]
+    index: usize,
+}
+
+#[derive(Args, Debug, Clone)]
+struct ConvertArgs {
+    /// The gsd file to read
+    #[clap()]
+    filename: String,
+
+    /// The gsd file to write the frames to
+    #[clap(parse(from_os_str))]
+    outfile: PathBuf,
+
+    /// The number of frames to write. By default this is all the frames which are sampled.
+    #[clap(short, long)]
+    num_frames: Option<usize>,
+
+    /// Skip this many frames between configurations which are written
+    #[clap(long, default_value = "1")]
+    skip_frames: usize,
+}
+
+impl From<&AnalyseArgs> for AnalysisConfig {
+    fn from(args: &AnalyseArgs) -> Self {
+        AnalysisConfig::default()
+            .compute_voronoi(args.voronoi)
+            .compute_order(!args.classify_only)
+            .num_neighbours(args.num_neighbours)
+    }
+}
+
+/// The additional metrics computed for each particle, which are each written to a column
+fn metrics(args: &AnalyseArgs) -> Vec<Box<dyn PerParticleMetric>> {
+    let mut metrics: Vec<Box<dyn PerParticleMetric>> = Vec::new();
+    for &l in args.steinhardt.iter() {
+        metrics.push(Box::new(SteinhardtOrder::new(l, args.num_neighbours)));
+    }
+    metrics
+}
+
+/// Load the model from a file when it exists, otherwise training it from the training files
+///
+/// Without a model file or any training data the model is left empty, classifying every particle
+/// as liquid.
+///
+fn load_model(
+    args: &AnalyseArgs,
+    config: &AnalysisConfig,
+) -> Result<KNN<[f32; 6], Classes>, Error> {
+    if let Some(model) = args.model.as_ref().filter(|m| m.exists()) {
+        return KNN::load(model)
+            .with_context(|| format!("Unable to load the model from {}", model.display()));
+    }
+    if args.training.is_empty() {
+        if let Some(model) = &args.model {
+            bail!(
+                "The model file {} doesn't exist and there are no training files to create it",
+                model.display()
+            );
+        }
+        return Ok(KNN::default());
+    }
+    let knn = run_training_with_config(args.training.clone(), args.training_index, config)?;
+    if let Some(model) = &args.model {
+        knn.save(model)?;
+    }
+    Ok(knn)
 }
 
 fn main() -> Result<(), Error> {
-    let args = Args::parse();
-    let nneighs = 6;
-    let compute_area = args.voronoi;
+    match Cli::parse().command {
+        Command::Analyse(args) => analyse(args),
+        Command::Train(args) => train(args),
+        Command::Convert(args) => convert(args),
+    }
+}
+
+fn train(args: TrainArgs) -> Result<(), Error> {
+    let config = AnalysisConfig::default();
+    let knn = run_training_with_config(args.training, args.index, &config)?;
+    knn.save(&args.output)
+}
 
-    let knn = Arc::new(run_training(args.training, 100)?);
+fn convert(args: ConvertArgs) -> Result<(), Error> {
+    decimate(
+        &args.filename,
+        &args.outfile,
+        args.skip_frames,
+        args.num_frames,
+    )
+}
+
+fn analyse(args: AnalyseArgs) -> Result<(), Error> {
+    if args.num_neighbours == 0 {
+        bail!("The order parameters require at least one neighbour");
+    }
+    let metrics = metrics(&args);
+    let metric_names: Vec<String> = metrics.iter().map(|m| m.name().to_string()).collect();
+    let mut format = RowFormat::default().metrics(metric_names.clone());
+    if let Some(precision) = args.precision {
+        format = format.precision(precision);
+    }
+    if !args.columns.is_empty() {
+        format = format.columns(args.columns.clone())?;
+    }
+    if args.classify_only {
+        format = format.columns(vec!["molecule".into(), "timestep".into(), "class".into()])?;
+    }
+    let config = Arc::new(AnalysisConfig::from(&args));
+    let knn = Arc::new(load_model(&args, &config)?);
+    let metrics = Arc::new(metrics);
 
     let trj = GSDTrajectory::new(&args.filename)?;
     let num_frames = match args.num_frames {
@@ -106,50 +255,162 @@ fn main() -> Result<(), Error> {
         None => trj.nframes() as usize / args.skip_frames,
     };
 
-    let (tx, rx) = std::sync::mpsc::channel::<CalcResult>();
+    if args.buffer == 0 {
+        bail!("The buffer needs to hold at least 1 frame");
+    }
+    let (tx, rx) =
+        std::sync::mpsc::sync_channel::<Result<(CalcResult, Timing), Error>>(args.buffer);
+    // A slot is taken before reading each frame and only released once the results of that frame
+    // have been written, so reading the trajectory blocks when the writer falls behind.
+    let (slot_tx, slot_rx) = std::sync::mpsc::sync_channel::<()>(args.buffer);
 
     let progress_bar = indicatif::ProgressBar::new(num_frames as u64).with_style(
         indicatif::ProgressStyle::default_bar()
             .template("{msg}{wide_bar} {per_sec} {pos}/{len} [{elapsed_precise}/{eta_precise}]"),
     );
-    let mut wtr = csv::Writer::from_path(args.outfile)?;
-    let writer_thread = std::thread::spawn(move || {
-        for frame_result in rx.iter() {
-            let results: Vec<Row> = frame_result.into();
-            for row in results {
-                wtr.serialize(row).expect("Serializing frame failed");
+    let header: Vec<String> = if args.summary {
+        Summary::header(&metric_names)
+    } else {
+        format.header()
+    }
+    .into_iter()
+    .map(String::from)
+    .collect();
+    // When appending to a file which already contains rows, the header is already present
+    let write_header =
+        !args.append || std::fs::metadata(&args.outfile).map_or(true, |m| m.len() == 0);
+    if !write_header {
+        let existing = csv::ReaderBuilder::new()
+            .has_headers(false)
+            .from_path(&args.outfile)?
+            .records()
+            .next()
+            .transpose()?
+            .unwrap_or_default();
+        if existing.iter().ne(header.iter()) {
+            bail!(
+                "Unable to append to {}, which has the columns {:?} rather than {:?}",
+                args.outfile.display(),
+                existing.iter().collect::<Vec<_>>(),
+                header
+            );
+        }
+    }
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(args.append)
+        .truncate(!args.append)
+        .open(&args.outfile)?;
+    // The rows are serialized as tuples, so the header is written separately
+    let mut wtr = csv::WriterBuilder::new()
+        .has_headers(false)
+        .from_writer(file);
+    let summary = args.summary;
+    let timing = args.timing;
+    if write_header {
+        wtr.write_record(&header)?;
+    }
+    // The writer stops at the first frame which fails, returning the error
+    let writer_thread = std::thread::spawn(move || -> Result<(), Error> {
+        let mut total_timing = Timing::default();
+        let mut timed_frames = 0;
+        for message in rx.iter() {
+            let (frame_result, frame_timing) = message?;
+            if timing {
+                print_timing(frame_result.timestep, &frame_timing);
+                total_timing += frame_timing;
+                timed_frames += 1;
+            }
+            if summary {
+                wtr.serialize(Summary::from(&frame_result))?;
+            } else {
+                let values: Vec<Vec<f32>> = (0..frame_result.class.len())
+                    .map(|index| frame_result.metric_values(index))
+                    .collect();
+                let results: Vec<Row> = frame_result.into();
+                for (row, values) in results.iter().zip(values.iter()) {
+                    wtr.serialize(format.format_with_metrics(row, values))?;
+                }
             }
             progress_bar.inc(1);
+            slot_rx.recv().expect("A slot is taken for every frame");
         }
-        wtr.flush().expect("Flushing file failed");
+        wtr.flush()?;
         progress_bar.finish();
+        if timing && timed_frames > 0 {
+            print_timing_summary(&total_timing, timed_frames);
+        }
+        Ok(())
     });
 
-    for frame in trj.step_by(args.skip_frames).take(num_frames) {
+    // The features are written to a separate file by another thread, only when requested
+    let (feature_tx, feature_thread) = match &args.dump_features {
+        Some(filename) => {
+            let (feature_tx, feature_rx) = std::sync::mpsc::channel::<Vec<FeatureRow>>();
+            let mut wtr = csv::Writer::from_path(filename)?;
+            let feature_thread = std::thread::spawn(move || {
+                for rows in feature_rx.iter() {
+                    for row in rows {
+                        wtr.serialize(row).expect("Serializing features failed");
+                    }
+                }
+                wtr.flush().expect("Flushing file failed");
+            });
+            (Some(feature_tx), Some(feature_thread))
+        }
+        None => (None, None),
+    };
+
+    // Reading stops at the first frame which can't be read, with the error returned once the
+    // frames already read have been written
+    let mut read = Ok(());
+    for index in (0..trj.nframes() as usize)
+        .step_by(args.skip_frames)
+        .take(num_frames)
+    {
+        let frame = match trj.get_frame(index as u64) {
+            Ok(frame) => frame,
+            Err(error) => {
+                read = Err(error.context(format!("Reading frame {} failed", index)));
+                break;
+            }
+        };
+        // The writer only stops receiving when it has stopped at an error
+        if slot_tx.send(()).is_err() {
+            break;
+        }
         let tx = tx.clone();
+        let feature_tx = feature_tx.clone();
         let k = knn.clone();
+        let config = config.clone();
+        let metrics = metrics.clone();
         rayon::spawn_fifo(move || {
-            let f = Frame::from(frame);
-            let orient_order = orientational_order(&f, nneighs);
-            let hexatic_order = hexatic_order(&f, nneighs);
-            assert_eq!(orient_order.len(), f.len());
-            let class = k
-                .predict(&extract_features(&f))
-                .unwrap_or_else(|_| vec![Classes::Liquid; f.len()]);
-            assert_eq!(class.len(), f.len());
-            let area = if compute_area {
-                Some(voronoi_area(&f).unwrap())
-            } else {
-                None
+            let analysis = || -> Result<(CalcResult, Timing), Error> {
+                let start = Instant::now();
+                let f = Frame::try_new(frame)?;
+                let create_frame = start.elapsed();
+                if let Some(feature_tx) = feature_tx {
+                    feature_tx
+                        .send(feature_rows(f.timestep as usize, &extract_features(&f)))
+                        .expect("channel will be there waiting for the pool");
+                }
+                let (result, timing) = analyse_frame_metrics(&f, &k, &config, &metrics)?;
+                assert!(result
+                    .orient_order
+                    .as_ref()
+                    .is_none_or(|order| order.len() == f.len()));
+                assert_eq!(result.class.len(), f.len());
+                let timing = Timing {
+                    create_frame,
+                    ..timing
+                };
+                Ok((result, timing))
             };
-            tx.send(CalcResult {
-                timestep: f.timestep as usize,
-                orient_order,
-                hexatic_order,
-                class,
-                area,
-            })
-            .expect("channel will be there waiting for the pool");
+            let result = analysis().with_context(|| format!("Analysing frame {} failed", index));
+            // After an error the writer stops receiving, so the results of any remaining frames
+            // are discarded
+            tx.send(result).unwrap_or(());
         });
     }
 
@@ -157,7 +418,39 @@ fn main() -> Result<(), Error> {
     // However, that still leaves the initial copy, so here the initial transmitter is dropped
     // which means the writer thread will no longer be waiting for a final value to be sent.
     drop(tx);
+    drop(feature_tx);
+
+    let written = writer_thread.join().expect("Joining threads failed");
+    if let Some(feature_thread) = feature_thread {
+        feature_thread.join().expect("Joining threads failed");
+    }
+    written.and(read)
+}
 
-    writer_thread.join().expect("Joining threads failed");
-    Ok(())
+/// Print the time taken by each stage of the analysis of a single frame
+fn print_timing(timestep: usize, timing: &Timing) {
+    let stages: Vec<String> = timing
+        .stages()
+        .iter()
+        .map(|(name, duration)| format!("{} {:.3} ms", name, duration.as_secs_f64() * 1e3))
+        .collect();
+    println!(
+        "timing timestep {}: {}, total {:.3} ms",
+        timestep,
+        stages.join(", "),
+        timing.total().as_secs_f64() * 1e3
+    );
+}
+
+/// Print a table of the total and mean time of each stage over all the frames
+fn print_timing_summary(total: &Timing, frames: u32) {
+    println!("{:<14}{:>14}{:>14}", "stage", "total (ms)", "mean (ms)");
+    for (name, duration) in total
+        .stages()
+        .iter()
+        .chain(std::iter::once(&("total", total.total())))
+    {
+        let ms = duration.as_secs_f64() * 1e3;
+        println!("{:<14}{:>14.3}{:>14.3}", name, ms, ms / f64::from(frames));
+    }
 }
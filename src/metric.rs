@@ -0,0 +1,85 @@
+//
+// metric.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Quantities computed for every particle which are written as additional columns of the output
+
+use crate::frame::Frame;
+use crate::steinhardt::steinhardt_order;
+
+/// A quantity which has a single value for each particle in a frame
+///
+/// Each metric is written as a column of the per-particle output, with the column named by
+/// [`PerParticleMetric::name`], so a new quantity is added to the output by implementing this
+/// trait rather than modifying the [`Row`](crate::analysis::Row).
+///
+pub trait PerParticleMetric: Send + Sync {
+    /// The name of the column containing the values
+    fn name(&self) -> &str;
+    /// Compute the value for every particle within the frame
+    fn compute(&self, frame: &Frame) -> Vec<f32>;
+}
+
+/// The Steinhardt bond order $q_l$ of each particle, see [`steinhardt_order`]
+pub struct SteinhardtOrder {
+    name: String,
+    l: usize,
+    num_neighbours: usize,
+}
+
+impl SteinhardtOrder {
+    /// The bond order of degree `l`, which is written to the column `q{l}`
+    pub fn new(l: usize, num_neighbours: usize) -> Self {
+        SteinhardtOrder {
+            name: format!("q{}", l),
+            l,
+            num_neighbours,
+        }
+    }
+}
+
+impl PerParticleMetric for SteinhardtOrder {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn compute(&self, frame: &Frame) -> Vec<f32> {
+        steinhardt_order(frame, self.l, self.num_neighbours)
+    }
+}
+
+/// Compute each of the metrics for a frame, pairing the values with the name of the metric
+pub fn compute_metrics(
+    frame: &Frame,
+    metrics: &[Box<dyn PerParticleMetric>],
+) -> Vec<(String, Vec<f32>)> {
+    metrics
+        .iter()
+        .map(|metric| (metric.name().to_string(), metric.compute(frame)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::square_lattice;
+
+    #[test]
+    fn compute_two_metrics() {
+        let frame = square_lattice(6);
+        let metrics: Vec<Box<dyn PerParticleMetric>> = vec![
+            Box::new(SteinhardtOrder::new(4, 4)),
+            Box::new(SteinhardtOrder::new(6, 4)),
+        ];
+        let values = compute_metrics(&frame, &metrics);
+
+        let names: Vec<&str> = values.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["q4", "q6"]);
+        for (_, v) in values.iter() {
+            assert_eq!(v.len(), frame.len());
+        }
+        assert_eq!(values[0].1, steinhardt_order(&frame, 4, 4));
+    }
+}
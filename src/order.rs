@@ -4,17 +4,69 @@
 // Distributed under terms of the MIT license.
 //
 
-use crate::frame::Frame;
-use nalgebra::{Complex, ComplexField, Point3, Rotation2, UnitQuaternion, Vector2};
+use crate::frame::{Frame, Region};
+use crate::voronoi::voronoi_neighbours;
+use anyhow::Error;
+use gsd::GSDTrajectory;
+use nalgebra::{Complex, ComplexField, Point3, Rotation2, UnitQuaternion, Vector2, Vector3};
 use num_traits::Zero;
+use rayon::prelude::*;
 
 pub fn num_neighbours(frame: &Frame, cutoff: f32) -> Vec<usize> {
     frame
-        .neighbours_cutoff(cutoff)
-        .map(|neighs| neighs.count())
+        .neighbour_list_cutoff(cutoff)
+        .iter()
+        .map(|neighs| neighs.len())
         .collect()
 }
 
+/// Estimate the local number density around each particle from its nearest neighbours
+///
+/// For a two dimensional configuration this is $k / \pi r_k^2$, where $r_k$ is the distance to
+/// the furthest of the $k$ = `num_neighbours` nearest neighbours, which is the number of
+/// particles within the circle containing those neighbours.
+///
+pub fn local_density(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    frame
+        .neighbour_list_n(num_neighbours)
+        .iter()
+        .enumerate()
+        .map(|(index, neighs)| {
+            let radius = neighs
+                .iter()
+                .map(|&n| frame.neighbour_vector(index, n).norm())
+                .fold(0., f32::max);
+            neighs.len() as f32 / (std::f32::consts::PI * radius * radius)
+        })
+        .collect()
+}
+
+/// The number of particles with each coordination number at a distance of `cutoff`
+///
+/// The index of the result is the number of neighbours, so the value at index 6 is the number of
+/// particles with 6 neighbours. The result extends to the largest coordination number within the
+/// frame, being empty for a frame without any particles.
+///
+pub fn coordination_histogram(frame: &Frame, cutoff: f32) -> Vec<usize> {
+    let counts = num_neighbours(frame, cutoff);
+    let mut histogram = vec![0; counts.iter().max().map_or(0, |&max| max + 1)];
+    for count in counts {
+        histogram[count] += 1;
+    }
+    histogram
+}
+
+/// The mean of the values from each neighbour
+///
+/// This divides by the number of neighbours found, rather than the number requested, so where a
+/// frame has too few particles every other particle is a neighbour. Without any neighbours the
+/// mean is 0.
+///
+fn neighbour_mean(values: impl Iterator<Item = f32>) -> f32 {
+    let (total, count) = values.fold((0., 0), |(total, count), v| (total + v, count + 1));
+    total / count.max(1) as f32
+}
+
 /// A Helper function to comptue the orientational order
 ///
 /// This provides a method by which to compute the orientational order. This is the component
@@ -25,31 +77,238 @@ pub fn num_neighbours(frame: &Frame, cutoff: f32) -> Vec<usize> {
 fn orientational_order_iter(
     reference: &UnitQuaternion<f32>,
     neighs: impl Iterator<Item = UnitQuaternion<f32>>,
-    num_neighbours: usize,
 ) -> f32 {
-    neighs.fold(0., |acc, i| acc + reference.angle_to(&i).cos().powi(2)) / num_neighbours as f32
+    neighbour_mean(neighs.map(|i| reference.angle_to(&i).cos().powi(2)))
+}
+
+/// A Helper function to compute the orientational order of head-tail symmetric molecules
+///
+/// Each molecule is described by the direction of its axis, with the contribution of each
+/// neighbour being the $\cos^2$ of the angle between the axes. Since this is unchanged by
+/// reversing the direction of either axis, the directors $\mathbf{n}$ and $-\mathbf{n}$ are
+/// equivalent.
+///
+/// Returns a values in the range [0,1]
+///
+fn orientational_order_director_iter(
+    reference: &Vector3<f32>,
+    neighs: impl Iterator<Item = Vector3<f32>>,
+) -> f32 {
+    neighbour_mean(neighs.map(|n| reference.dot(&n).powi(2)))
 }
 
 /// This computes the orientational order paramter for every particle in a configuration.
 ///
 /// The orientational order parameter, is the relative orientation of the `num_neighbours`
-/// nearest particles converted into a one dimensional paramter.
+/// nearest particles converted into a one dimensional paramter. Where the frame has too few
+/// particles, every other particle is a neighbour.
 ///
 pub fn orientational_order(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
     // Calculate the orientational_order parameter for each particle
+    frame
+        .neighbour_list_n(num_neighbours)
+        .iter()
+        .enumerate()
+        .map(|(index, neighs)| {
+            orientational_order_iter(
+                &frame.orientation[index],
+                neighs.iter().map(|&n| frame.orientation[n]),
+            )
+        })
+        .collect()
+}
+
+/// Compute the orientational order weighting each neighbour by the inverse of its distance
+///
+/// This is the same as [`orientational_order`], except the contribution of each of the
+/// `num_neighbours` nearest particles is weighted by $1/r$, normalised by the total weight, so
+/// the closer neighbours have a larger influence. Where all the neighbours are the same distance
+/// away this gives the same values as [`orientational_order`].
+///
+pub fn orientational_order_weighted(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    frame
+        .neighbour_list_n(num_neighbours)
+        .iter()
+        .enumerate()
+        .map(|(index, neighs)| {
+            let reference = &frame.orientation[index];
+            let (total, weights) = neighs.iter().fold((0., 0.), |(total, weights), &n| {
+                let weight = 1. / frame.neighbour_vector(index, n).norm();
+                let order = reference.angle_to(&frame.orientation[n]).cos().powi(2);
+                (total + weight * order, weights + weight)
+            });
+            // A particle without any neighbours has no order, like in orientational_order
+            if weights == 0. {
+                0.
+            } else {
+                total / weights
+            }
+        })
+        .collect()
+}
+
+/// Compute the orientational order for every frame in a trajectory
+///
+/// This yields the timestep of each frame along with the [`orientational_order`] of the
+/// particles, sampling every `skip` frames of the trajectory, which is convenient for plotting
+/// the change of the order over time. The value of `skip` has to be at least 1. A frame which
+/// can't be read, or which has non-finite positions, gives an error in place of its values.
+///
+pub fn orientational_order_series(
+    traj: GSDTrajectory,
+    num_neighbours: usize,
+    skip: usize,
+) -> impl Iterator<Item = Result<(u64, Vec<f32>), Error>> {
+    (0..traj.nframes()).step_by(skip).map(move |index| {
+        let frame = Frame::try_new(traj.get_frame(index)?)?;
+        Ok((frame.timestep, orientational_order(&frame, num_neighbours)))
+    })
+}
+
+/// Compute the orientational order for every particle in parallel
+///
+/// This gives the same values as [`orientational_order`], with the particles divided between the
+/// threads of the rayon thread pool, each of which queries the neighbours of its particles.
+///
+pub fn orientational_order_par(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    (0..frame.len())
+        .into_par_iter()
+        .map(|index| {
+            orientational_order_iter(
+                &frame.orientation[index],
+                frame
+                    .particle_neighbours_n(index, num_neighbours)
+                    .map(|n| frame.orientation[n]),
+            )
+        })
+        .collect()
+}
+
+/// Compute the orientational order for every particle, treating the molecules as head-tail symmetric
+///
+/// Rather than the angle of rotation between the orientations of the particles, this compares the
+/// directors of the molecules, which are the `axis` of the molecule in the body frame rotated by
+/// the orientation of each particle. The order is the same for a molecule pointing in either
+/// direction along the director, suitable for rod-like molecules with nematic symmetry.
+///
+pub fn orientational_order_director(
+    frame: &Frame,
+    num_neighbours: usize,
+    axis: &Vector3<f32>,
+) -> Vec<f32> {
+    let axis = axis.normalize();
+    let directors: Vec<Vector3<f32>> = frame.orientation.iter().map(|q| q * axis).collect();
+    frame
+        .neighbour_list_n(num_neighbours)
+        .iter()
+        .enumerate()
+        .map(|(index, neighs)| {
+            orientational_order_director_iter(
+                &directors[index],
+                neighs.iter().map(|&n| directors[n]),
+            )
+        })
+        .collect()
+}
+
+/// Compute the orientational order from the raw quaternion components of the neighbours
+///
+/// The angle $\theta$ between two unit quaternions satisfies $\cos(\theta/2) = |q_1 \cdot q_2|$,
+/// so $\cos^2\theta = (2 (q_1 \cdot q_2)^2 - 1)^2$. This only requires the dot products, which
+/// are computed in a loop over plain arrays that the compiler is able to vectorise.
+///
+fn orientational_order_dot(reference: &[f32; 4], neighs: &[[f32; 4]]) -> f32 {
+    let mut total = 0.;
+    for n in neighs {
+        let dot =
+            reference[0] * n[0] + reference[1] * n[1] + reference[2] * n[2] + reference[3] * n[3];
+        let cos = 2. * dot * dot - 1.;
+        total += cos * cos;
+    }
+    total / neighs.len().max(1) as f32
+}
+
+/// Compute the orientational order for every particle using batched dot products
+///
+/// This gives the same values as [`orientational_order`], however it avoids the conversions
+/// through nalgebra's `UnitQuaternion` for each pair of particles, instead operating on the
+/// quaternion components directly.
+///
+pub fn orientational_order_batched(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    let orientations: Vec<[f32; 4]> = frame
+        .orientation
+        .iter()
+        .map(|q| q.as_ref().coords.into())
+        .collect();
+    let mut buffer = Vec::with_capacity(num_neighbours);
     frame
         .neighbours_n(num_neighbours)
         .enumerate()
         .map(|(index, neighs)| {
+            buffer.clear();
+            buffer.extend(neighs.map(|n| orientations[n]));
+            orientational_order_dot(&orientations[index], &buffer)
+        })
+        .collect()
+}
+
+/// Compute the orientational order for every particle, only considering neighbours of the same type
+///
+/// This is the same as [`orientational_order`], except the neighbours of each particle are the
+/// `num_neighbours` nearest particles with the same type, so in a mixture the order of each
+/// species is independent of the other species.
+///
+pub fn orientational_order_by_type(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    (0..frame.len())
+        .map(|index| {
             orientational_order_iter(
                 &frame.orientation[index],
-                neighs.map(|n| frame.orientation[n]),
-                num_neighbours,
+                frame
+                    .particle_neighbours_same_type(index, num_neighbours)
+                    .map(|n| frame.orientation[n]),
             )
         })
         .collect()
 }
 
+/// Compute the orientational order for a subset of the particles in a configuration
+///
+/// Only the particles in `indices` have the orientational order calculated, however the neighbours
+/// are still found from all the particles in the frame, so the values are identical to those from
+/// [`orientational_order`]. Each value is returned alongside the index of the particle.
+///
+pub fn orientational_order_subset(
+    frame: &Frame,
+    num_neighbours: usize,
+    indices: &[usize],
+) -> Vec<(usize, f32)> {
+    indices
+        .iter()
+        .map(|&index| {
+            let order = orientational_order_iter(
+                &frame.orientation[index],
+                frame
+                    .particle_neighbours_n(index, num_neighbours)
+                    .map(|n| frame.orientation[n]),
+            );
+            (index, order)
+        })
+        .collect()
+}
+
+/// Compute the orientational order of only the particles within a region of the simulation cell
+///
+/// This is [`orientational_order_subset`] for the particles within the `region`, so the
+/// neighbours are still found from all the particles in the frame.
+///
+pub fn orientational_order_in_region(
+    frame: &Frame,
+    num_neighbours: usize,
+    region: &Region,
+) -> Vec<(usize, f32)> {
+    orientational_order_subset(frame, num_neighbours, &frame.indices_in_region(region))
+}
+
 /// A Helper function to comptue the hexatic order
 ///
 /// $$ \psi_k = \frac{1}{k} \sum_j^n \exp{i k \theta} $$
@@ -64,19 +323,30 @@ fn hexatic_order_iter(
     neighs: impl Iterator<Item = Point3<f32>>,
     num_neighbours: usize,
 ) -> f32 {
+    hexatic_order_complex(neighs.map(|p| p - reference), num_neighbours)
+        .norm_sqr()
+        .sqrt()
+}
+
+/// The complex value of the hexatic order from the bonds to each neighbour
+///
+/// The phase is $k$ times the angle of the bonds relative to the y axis.
+///
+fn hexatic_order_complex(
+    bonds: impl Iterator<Item = Vector3<f32>>,
+    num_neighbours: usize,
+) -> Complex<f32> {
     let reference_vec = Vector2::new(0., 1.);
-    neighs
-        .map(|p| p - reference)
+    let (total, count) = bonds
         // Calculate the rotation between two vectors
         .map(|v| Rotation2::rotation_between(&reference_vec.xy(), &v.xy()))
         // Convert the multiplied angle into a UnitComplex (rotation), then downcast to Complex
         .map(|a| Complex::new(0., a.angle() * num_neighbours as f32).exp())
-        // Average all the complex numbers
-        .fold(Complex::<f32>::zero(), |acc, i| {
-            acc + i / num_neighbours as f32
-        })
-        .norm_sqr()
-        .sqrt()
+        .fold((Complex::<f32>::zero(), 0), |(total, count), i| {
+            (total + i, count + 1)
+        });
+    // Average all the complex numbers, using the neighbours found like [`neighbour_mean`]
+    total / count.max(1) as f32
 }
 
 /// Compute the hexatic order for every particle in a configuration
@@ -90,23 +360,438 @@ fn hexatic_order_iter(
 ///
 pub fn hexatic_order(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
     frame
-        .neighbours_n(num_neighbours)
+        .neighbour_list_n(num_neighbours)
+        .iter()
         .enumerate()
         .map(|(index, neighs)| {
             hexatic_order_iter(
                 &frame.position[index],
-                neighs.map(|i| frame.position[i]),
+                neighs.iter().map(|&i| frame.position[i]),
                 num_neighbours,
             )
         })
         .collect()
 }
 
+/// Compute the hexatic order for every particle in parallel
+///
+/// This gives the same values as [`hexatic_order`], dividing the particles between threads in the
+/// same way as [`orientational_order_par`].
+///
+pub fn hexatic_order_par(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    (0..frame.len())
+        .into_par_iter()
+        .map(|index| {
+            hexatic_order_iter(
+                &frame.position[index],
+                frame
+                    .particle_neighbours_n(index, num_neighbours)
+                    .map(|n| frame.position[n]),
+                num_neighbours,
+            )
+        })
+        .collect()
+}
+
+/// Compute the global hexatic order of a configuration
+///
+/// This is the average of the complex hexatic order of every particle
+///
+/// $$ \Psi_k = \frac{1}{N} \sum_j^N \psi_{k,j} $$
+///
+/// using the bonds to the `num_neighbours` nearest particles. The
+/// magnitude is close to 1 when the bonds throughout the configuration share an orientation, while
+/// the phase gives that orientation, being $k$ times the angle of the bonds relative to the y axis.
+///
+pub fn global_hexatic_order(frame: &Frame, num_neighbours: usize) -> Complex<f32> {
+    if frame.is_empty() {
+        return Complex::zero();
+    }
+    frame
+        .neighbour_list_n(num_neighbours)
+        .iter()
+        .enumerate()
+        .map(|(index, neighs)| {
+            hexatic_order_complex(
+                neighs.iter().map(|&n| frame.neighbour_vector(index, n)),
+                num_neighbours,
+            )
+        })
+        .fold(Complex::<f32>::zero(), |acc, i| acc + i)
+        / frame.len() as f32
+}
+
+/// The orientation of the local lattice around each particle from the phase of the hexatic order
+///
+/// This is $\arg(\psi_k) / k$, where $k$ is the `num_neighbours` used for [`hexatic_order`],
+/// folded into the range $[0, 2\pi / k)$ from the symmetry of the lattice, so for 6 neighbours the
+/// orientation is within $[0, \pi / 3)$. The particles within a single crystal grain have the
+/// same orientation, with the angle measured relative to the y axis.
+///
+pub fn local_lattice_orientation(frame: &Frame, num_neighbours: usize) -> Vec<f32> {
+    let period = 2. * std::f32::consts::PI / num_neighbours as f32;
+    frame
+        .neighbour_list_n(num_neighbours)
+        .iter()
+        .enumerate()
+        .map(|(index, neighs)| {
+            let psi = hexatic_order_complex(
+                neighs.iter().map(|&n| frame.neighbour_vector(index, n)),
+                num_neighbours,
+            );
+            (psi.im.atan2(psi.re) / num_neighbours as f32).rem_euclid(period)
+        })
+        .collect()
+}
+
+/// The rotation of the lattice between two frames from the phase of the global hexatic order
+///
+/// This is the difference of $\arg(\Psi_k) / k$ of [`global_hexatic_order`] between the frames
+/// `a` and `b`, where $k$ is `num_neighbours`. From the $k$-fold symmetry of the lattice the
+/// rotation is only known up to a multiple of $2\pi / k$, so the value is folded into the range
+/// $[-\pi / k, \pi / k)$, with a positive value being an anticlockwise rotation.
+///
+pub fn hexatic_phase_drift(a: &Frame, b: &Frame, num_neighbours: usize) -> f32 {
+    let k = num_neighbours as f32;
+    let phase = |frame: &Frame| {
+        let psi = global_hexatic_order(frame, num_neighbours);
+        psi.im.atan2(psi.re)
+    };
+    let period = 2. * std::f32::consts::PI / k;
+    ((phase(b) - phase(a)) / k + period / 2.).rem_euclid(period) - period / 2.
+}
+
+/// Compute the hexatic order for every particle, weighting each neighbour by the voronoi edge length
+///
+/// Rather than a fixed number of neighbours, this uses the voronoi neighbours of each particle,
+/// weighting the contribution of each by the length $l_j$ of the shared voronoi edge
+///
+/// $$ \psi_6 = \sum_j \frac{l_j}{L} \exp{6 i \theta_j} $$
+///
+/// where $L$ is the perimeter of the voronoi cell, as described by Mickel et al. This makes the
+/// value robust to small changes in the positions of the particles.
+///
+pub fn hexatic_order_weighted(frame: &Frame) -> Result<Vec<f32>, Error> {
+    Ok(voronoi_neighbours(frame)
+        .into_iter()
+        .enumerate()
+        .map(|(index, neighbours)| {
+            let perimeter: f32 = neighbours.iter().map(|(_, length)| length).sum();
+            neighbours
+                .iter()
+                .map(|&(neighbour, length)| {
+                    let bond = frame.neighbour_vector(index, neighbour);
+                    let angle = bond.y.atan2(bond.x);
+                    Complex::new(0., 6. * angle).exp() * length / perimeter
+                })
+                .fold(Complex::<f32>::zero(), |acc, i| acc + i)
+                .norm_sqr()
+                .sqrt()
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::distance::min_image;
+    use crate::test_utils::triangular_lattice;
     use approx::assert_abs_diff_eq;
+    use gsd::GSDFrame;
+    use itertools::iproduct;
     use proptest::prelude::*;
+    use std::f32::consts::PI;
+
+    #[test]
+    fn too_few_particles() {
+        use crate::steinhardt::{averaged_steinhardt_order, steinhardt_order};
+
+        let position = vec![[0.; 3], [1., 0., 0.], [0., 1., 0.], [1., 1., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [4., 4., 1., 0., 0., 0.]).into();
+
+        // Every other particle is a neighbour, so aligned particles are still perfectly ordered
+        let order = orientational_order(&frame, 6);
+        assert_eq!(order, orientational_order(&frame, 3));
+        for value in order.iter() {
+            assert_abs_diff_eq!(*value, 1., epsilon = 1e-6);
+        }
+        // Every method of computing the order gives the same values
+        for (value, other) in order.iter().zip(orientational_order_par(&frame, 6)) {
+            assert_abs_diff_eq!(*value, other, epsilon = 1e-6);
+        }
+        for (value, other) in order.iter().zip(orientational_order_batched(&frame, 6)) {
+            assert_abs_diff_eq!(*value, other, epsilon = 1e-6);
+        }
+        assert_eq!(hexatic_order(&frame, 6), hexatic_order_par(&frame, 6));
+
+        // The bond order is averaged over the 3 neighbours found
+        for l in [4, 6] {
+            assert_eq!(
+                steinhardt_order(&frame, l, 6),
+                steinhardt_order(&frame, l, 3)
+            );
+            assert_eq!(
+                averaged_steinhardt_order(&frame, l, 6),
+                averaged_steinhardt_order(&frame, l, 3)
+            );
+        }
+    }
+
+    fn test_frame() -> Result<Frame, Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        Ok(GSDTrajectory::new(filename)?.get_frame(1)?.into())
+    }
+
+    #[test]
+    fn num_neighbours_excludes_self() {
+        let position = vec![[0., 0., 0.], [5., 5., 0.], [5.5, 5., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; 3];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [20., 20., 1., 0., 0., 0.]).into();
+        // The first particle is isolated, while the others are each other's only neighbour
+        assert_eq!(num_neighbours(&frame, 1.), vec![0, 1, 1]);
+        assert_eq!(num_neighbours(&triangular_lattice(6, 0.), 1.1), vec![6; 36]);
+    }
+
+    #[test]
+    fn local_density_lattice() {
+        // The triangular lattice with a unit spacing has 6 neighbours within a distance of 1
+        for value in local_density(&triangular_lattice(6, 0.), 6) {
+            assert_abs_diff_eq!(value, 6. / PI, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn coordination_histogram_hexagonal() {
+        let frame = triangular_lattice(6, 0.);
+        assert_eq!(
+            coordination_histogram(&frame, 1.1),
+            vec![0, 0, 0, 0, 0, 0, 36]
+        );
+        // Including the second shell of neighbours at a distance of sqrt(3)
+        assert_eq!(coordination_histogram(&frame, 1.8)[12], 36);
+
+        let position = vec![[0., 0., 0.], [5., 5., 0.], [5.5, 5., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; 3];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [20., 20., 1., 0., 0., 0.]).into();
+        assert_eq!(coordination_histogram(&frame, 1.), vec![1, 2]);
+    }
+
+    #[test]
+    fn orientational_order_batched_matches() -> Result<(), Error> {
+        let frame = test_frame()?;
+        let expected = orientational_order(&frame, 6);
+        let batched = orientational_order_batched(&frame, 6);
+        assert_eq!(expected.len(), batched.len());
+        for (e, b) in expected.iter().zip(batched.iter()) {
+            assert_abs_diff_eq!(e, b, epsilon = 1e-4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn orientational_order_by_type_independent() {
+        // The first species is on a lattice with varied orientations, while the second species
+        // fills the gaps with another orientation, with the positions then shuffled.
+        let mixture = |shift: f32| -> Frame {
+            let mut position = Vec::new();
+            let mut orientation = Vec::new();
+            for i in 0..64 {
+                let (x, y) = ((i % 8) as f32 - 4., (i / 8) as f32 - 4.);
+                let angle = i as f32 * 0.3;
+                position.push([x, y, 0.]);
+                orientation.push([angle.cos(), 0., 0., angle.sin()]);
+            }
+            for i in 0..64 {
+                let (x, y) = ((i % 8) as f32 - 3.5, (i / 8) as f32 - 3.5);
+                position.push([x + shift * (i as f32).sin(), y + shift, 0.]);
+                orientation.push([0., 0., 0., 1.]);
+            }
+            let mut frame = GSDFrame::from_arrays(position, orientation, [8., 8., 1., 0., 0., 0.]);
+            for t in frame.typeid[64..].iter_mut() {
+                *t = 1;
+            }
+            frame.into()
+        };
+        // The 4 nearest neighbours on the lattice, avoiding any ties
+        let reference = orientational_order_by_type(&mixture(0.), 4);
+        let rearranged = orientational_order_by_type(&mixture(0.3), 4);
+        for (r, a) in reference[..64].iter().zip(rearranged[..64].iter()) {
+            assert_abs_diff_eq!(r, a, epsilon = 1e-6);
+        }
+        // Without considering the types the order of the first species is changed
+        assert_ne!(
+            orientational_order(&mixture(0.), 4)[..64],
+            orientational_order(&mixture(0.3), 4)[..64]
+        );
+    }
+
+    #[test]
+    fn global_hexatic_order_grains() {
+        let aligned = global_hexatic_order(&triangular_lattice(10, 0.), 6);
+        assert_abs_diff_eq!(aligned.norm_sqr().sqrt(), 1., epsilon = 1e-4);
+
+        // Two separate grains, with the second rotated by 30 degrees, reversing the sign of the
+        // hexatic order of each particle.
+        let grain: Vec<Point3<f32>> = triangular_lattice(10, 0.).position;
+        let rotation = Rotation2::new(30_f32.to_radians());
+        let position: Vec<[f32; 3]> = grain
+            .iter()
+            .map(|p| [p.x - 30., p.y, 0.])
+            .chain(grain.iter().map(|p| {
+                let r = rotation * p.xy().coords;
+                [r.x + 20., r.y, 0.]
+            }))
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [100., 100., 1., 0., 0., 0.]).into();
+        let misaligned = global_hexatic_order(&frame, 6);
+        assert_abs_diff_eq!(misaligned.norm_sqr().sqrt(), 0., epsilon = 0.05);
+    }
+
+    #[test]
+    fn hexatic_phase_drift_rotation() {
+        // A single grain within a large cell, where the noise prevents ties in the neighbours
+        let grain: Vec<Point3<f32>> = triangular_lattice(10, 0.02).position;
+        let rotated = |degrees: f32| -> Frame {
+            let rotation = Rotation2::new(degrees.to_radians());
+            let position: Vec<[f32; 3]> = grain
+                .iter()
+                .map(|p| {
+                    let r = rotation * p.xy().coords;
+                    [r.x, r.y, 0.]
+                })
+                .collect();
+            let orientation = vec![[1., 0., 0., 0.]; position.len()];
+            GSDFrame::from_arrays(position, orientation, [100., 100., 1., 0., 0., 0.]).into()
+        };
+        let reference = rotated(0.);
+        assert_abs_diff_eq!(hexatic_phase_drift(&reference, &reference, 6), 0.);
+        let drift = hexatic_phase_drift(&reference, &rotated(12.), 6);
+        assert_abs_diff_eq!(drift, 12_f32.to_radians(), epsilon = 1e-4);
+        let drift = hexatic_phase_drift(&rotated(12.), &reference, 6);
+        assert_abs_diff_eq!(drift, -12_f32.to_radians(), epsilon = 1e-4);
+        // A rotation of 50 degrees is indistinguishable from -10 degrees
+        let drift = hexatic_phase_drift(&reference, &rotated(50.), 6);
+        assert_abs_diff_eq!(drift, -10_f32.to_radians(), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn local_lattice_orientation_grains() {
+        // Two separate grains, with the second rotated by 20 degrees
+        let grain: Vec<Point3<f32>> = triangular_lattice(10, 0.).position;
+        let rotation = Rotation2::new(20_f32.to_radians());
+        let position: Vec<[f32; 3]> = grain
+            .iter()
+            .map(|p| [p.x - 30., p.y, 0.])
+            .chain(grain.iter().map(|p| {
+                let r = rotation * p.xy().coords;
+                [r.x + 20., r.y, 0.]
+            }))
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [100., 100., 1., 0., 0., 0.]).into();
+        let angles = local_lattice_orientation(&frame, 6);
+        let period = PI / 3.;
+        for angle in angles.iter() {
+            assert!((0. ..period).contains(angle));
+        }
+
+        // The particles away from the edges of each grain have the same orientation
+        let interior = |offset: usize| -> Vec<f32> {
+            iproduct!(2..8, 2..8)
+                .map(|(x, y)| angles[offset + y * 10 + x])
+                .collect()
+        };
+        let first = interior(0);
+        let second = interior(100);
+        for angle in first.iter() {
+            assert_abs_diff_eq!(*angle, first[0], epsilon = 1e-4);
+        }
+        for angle in second.iter() {
+            assert_abs_diff_eq!(*angle, second[0], epsilon = 1e-4);
+        }
+        let difference = (second[0] - first[0]).rem_euclid(period);
+        assert_abs_diff_eq!(difference, 20_f32.to_radians(), epsilon = 1e-4);
+    }
+
+    #[test]
+    fn orientational_order_weighted_range() -> Result<(), Error> {
+        for value in orientational_order_weighted(&test_frame()?, 6) {
+            assert!((0. ..=1.).contains(&value), "{} out of range", value);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn orientational_order_weighted_equal_distances() {
+        // A square lattice where the 4 nearest neighbours are all at a distance of 1
+        let position: Vec<[f32; 3]> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| [x as f32, y as f32, 0.]))
+            .collect();
+        let orientation = (0..position.len())
+            .map(|i| {
+                let (s, c) = (i as f32 * 0.37).sin_cos();
+                [c, 0., 0., s]
+            })
+            .collect();
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [8., 8., 1., 0., 0., 0.]).into();
+        let weighted = orientational_order_weighted(&frame, 4);
+        let unweighted = orientational_order(&frame, 4);
+        for (w, u) in weighted.iter().zip(unweighted.iter()) {
+            assert_abs_diff_eq!(w, u, epsilon = 1e-5);
+        }
+    }
+
+    #[test]
+    fn orientational_order_weighted_single_particle() {
+        let frame: Frame = GSDFrame::from_arrays(
+            vec![[0.; 3]],
+            vec![[1., 0., 0., 0.]],
+            [4., 4., 1., 0., 0., 0.],
+        )
+        .into();
+        assert_eq!(orientational_order_weighted(&frame, 6), vec![0.]);
+        assert_eq!(orientational_order(&frame, 6), vec![0.]);
+    }
+
+    #[test]
+    fn hexatic_order_weighted_perfect() -> Result<(), Error> {
+        for value in hexatic_order_weighted(&triangular_lattice(10, 0.))? {
+            assert_abs_diff_eq!(value, 1., epsilon = 1e-4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn hexatic_order_weighted_noise() -> Result<(), Error> {
+        let frame = triangular_lattice(10, 0.12);
+        let deviation = |values: Vec<f32>| {
+            values.iter().map(|v| (1. - v).abs()).sum::<f32>() / values.len() as f32
+        };
+        let weighted = deviation(hexatic_order_weighted(&frame)?);
+        let fixed = deviation(
+            (0..frame.len())
+                .map(|index| {
+                    hexatic_order_iter(
+                        &frame.position[index],
+                        frame
+                            .particle_neighbours_n(index, 6)
+                            .map(|n| frame.position[index] + frame.neighbour_vector(index, n)),
+                        6,
+                    )
+                })
+                .collect(),
+        );
+        assert!(weighted < fixed, "weighted {} fixed {}", weighted, fixed);
+        Ok(())
+    }
 
     #[test]
     fn hexatic_order_perfect() {
@@ -163,7 +848,7 @@ mod tests {
             .into_iter()
             .map(|a| UnitQuaternion::from_euler_angles(0., 0., a));
 
-        let orient_order: f32 = orientational_order_iter(&reference, points, 6);
+        let orient_order: f32 = orientational_order_iter(&reference, points);
         assert_abs_diff_eq!(orient_order, 1.);
     }
 
@@ -177,11 +862,66 @@ mod tests {
                 .into_iter()
                 .map(|a| UnitQuaternion::from_euler_angles(0., 0., a));
 
-            let orient_order: f32 = orientational_order_iter(&reference, points, 6);
+            let orient_order: f32 = orientational_order_iter(&reference, points);
             assert_abs_diff_eq!(orient_order, 1.);
         }
     }
 
+    #[test]
+    /// Ensure a director and its reverse are equivalent
+    fn orientational_order_director_symmetric() {
+        let axis = Vector3::x();
+        let reference = UnitQuaternion::identity();
+        // Reversing the director by an in plane and out of plane rotation
+        let flipped = [
+            UnitQuaternion::from_euler_angles(0., 0., PI),
+            UnitQuaternion::from_euler_angles(0., PI, 0.),
+        ];
+        let order = orientational_order_director_iter(
+            &(reference * axis),
+            flipped.iter().map(|q| q * axis),
+        );
+        assert_abs_diff_eq!(order, 1., epsilon = 1e-6);
+
+        // A rotation about the director leaves the molecule unchanged
+        let spun = UnitQuaternion::from_euler_angles(PI / 2., 0., 0.);
+        let order =
+            orientational_order_director_iter(&(reference * axis), std::iter::once(spun * axis));
+        assert_abs_diff_eq!(order, 1., epsilon = 1e-6);
+        let order = orientational_order_iter(&reference, std::iter::once(spun));
+        assert_abs_diff_eq!(order, 0., epsilon = 1e-6);
+
+        // Perpendicular molecules have no order
+        let perpendicular = UnitQuaternion::from_euler_angles(0., 0., PI / 2.);
+        let order = orientational_order_director_iter(
+            &(reference * axis),
+            std::iter::once(perpendicular * axis),
+        );
+        assert_abs_diff_eq!(order, 0., epsilon = 1e-6);
+    }
+
+    #[test]
+    fn parallel_matches_serial() -> Result<(), Error> {
+        let frame = test_frame()?;
+        assert_eq!(
+            orientational_order_par(&frame, 6),
+            orientational_order(&frame, 6)
+        );
+        assert_eq!(hexatic_order_par(&frame, 6), hexatic_order(&frame, 6));
+        Ok(())
+    }
+
+    #[test]
+    fn orientational_order_director_planar() -> Result<(), Error> {
+        // For rotations in the plane both measures are the same
+        let frame = test_frame()?;
+        let director = orientational_order_director(&frame, 6, &Vector3::x());
+        for (d, o) in director.iter().zip(orientational_order(&frame, 6)) {
+            assert_abs_diff_eq!(*d, o, epsilon = 1e-4);
+        }
+        Ok(())
+    }
+
     proptest! {
         #[test]
         /// Ensure values well behaved [0, 1]
@@ -191,9 +931,76 @@ mod tests {
                 .into_iter()
                 .map(|a| UnitQuaternion::from_euler_angles(0., 0., a));
 
-            let orient_order: f32 = orientational_order_iter(&reference, points, 6);
+            let orient_order: f32 = orientational_order_iter(&reference, points);
             assert!(0. <= orient_order);
             assert!(orient_order <= 1.);
         }
     }
+
+    #[test]
+    fn orientational_order_subset_matches() -> Result<(), Error> {
+        let frame = test_frame()?;
+        let full = orientational_order(&frame, 6);
+        let indices = [0, 10, 100, 1000, 4031];
+        let subset = orientational_order_subset(&frame, 6, &indices);
+        assert_eq!(subset.len(), indices.len());
+        for ((index, value), &expected_index) in subset.into_iter().zip(indices.iter()) {
+            assert_eq!(index, expected_index);
+            assert_abs_diff_eq!(value, full[index]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn orientational_order_in_region_slab() -> Result<(), Error> {
+        let frame = test_frame()?;
+        let region = Region::Slab {
+            axis: 0,
+            min: -2.,
+            max: 3.,
+        };
+        let values = orientational_order_in_region(&frame, 6, &region);
+        // Only the particles with a wrapped x coordinate within the slab are included
+        let within: Vec<usize> = (0..frame.len())
+            .filter(|&index| {
+                let x = min_image(&frame.simulation_cell, &frame.position[index].coords.into())[0];
+                (-2. ..3.).contains(&x)
+            })
+            .collect();
+        assert!(!within.is_empty() && within.len() < frame.len());
+        let indices: Vec<usize> = values.iter().map(|&(index, _)| index).collect();
+        assert_eq!(indices, within);
+        // The neighbours are from the whole frame, giving the same values as all the particles
+        let expected = orientational_order(&frame, 6);
+        for (index, value) in values {
+            assert_abs_diff_eq!(value, expected[index]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn orientational_order_series_timesteps() -> Result<(), Error> {
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+        let series: Vec<(u64, Vec<f32>)> =
+            orientational_order_series(GSDTrajectory::new(filename)?, 6, 3)
+                .collect::<Result<_, _>>()?;
+        // The frames 0, 3, 6 and 9 of the 10 in the trajectory
+        assert_eq!(series.len(), 4);
+        for window in series.windows(2) {
+            assert!(window[0].0 < window[1].0);
+        }
+        assert!(series.iter().all(|(_, order)| order.len() == 4032));
+        Ok(())
+    }
+
+    #[test]
+    fn orientational_order_series_invalid_frame() -> Result<(), Error> {
+        // The second frame has a particle with a non-finite position
+        let filename = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/non-finite.gsd");
+        let series: Vec<bool> = orientational_order_series(GSDTrajectory::new(filename)?, 6, 1)
+            .map(|values| values.is_ok())
+            .collect();
+        assert_eq!(series, vec![true, false]);
+        Ok(())
+    }
 }
@@ -0,0 +1,95 @@
+//
+// results.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Read the per-particle output of an analysis back into the results of each frame
+
+use crate::analysis::{CalcResult, Row};
+use anyhow::Error;
+use std::path::Path;
+
+/// Collect optional values, which are only present when every particle has a value
+fn collect_optional<T>(values: impl Iterator<Item = Option<T>>) -> Option<Vec<T>> {
+    values.collect()
+}
+
+/// Combine the rows of a single frame into the result for that frame
+fn group_rows(rows: Vec<Row>) -> CalcResult {
+    CalcResult {
+        timestep: rows[0].timestep,
+        orient_order: collect_optional(rows.iter().map(|r| r.orient_order)),
+        hexatic_order: collect_optional(rows.iter().map(|r| r.hexatic_order)),
+        class: rows.iter().map(|r| r.class).collect(),
+        area: collect_optional(rows.iter().map(|r| r.area)),
+        metrics: Vec::new(),
+    }
+}
+
+/// Load the per-particle csv output of an analysis, grouping the rows of each frame
+///
+/// Only the columns of a [`Row`] are loaded, with the columns of any additional metrics ignored.
+/// The rows of each frame are written consecutively, so a new frame starts whenever the timestep
+/// changes. The frames are returned in the order they appear within the file.
+///
+pub fn load_results<P: AsRef<Path>>(path: P) -> Result<Vec<CalcResult>, Error> {
+    let mut reader = csv::Reader::from_path(path)?;
+    let mut results = Vec::new();
+    let mut frame: Vec<Row> = Vec::new();
+    for row in reader.deserialize() {
+        let row: Row = row?;
+        if frame.last().is_some_and(|r| r.timestep != row.timestep) {
+            results.push(group_rows(std::mem::take(&mut frame)));
+        }
+        frame.push(row);
+    }
+    if !frame.is_empty() {
+        results.push(group_rows(frame));
+    }
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::learning::Classes;
+
+    #[test]
+    fn results_roundtrip() -> Result<(), Error> {
+        let results = vec![
+            CalcResult {
+                timestep: 0,
+                orient_order: Some(vec![0.1, 0.2, 0.3]),
+                hexatic_order: Some(vec![0.5, 0.6, 0.7]),
+                class: vec![Classes::Liquid, Classes::P2, Classes::PG],
+                area: None,
+                metrics: Vec::new(),
+            },
+            CalcResult {
+                timestep: 100,
+                orient_order: Some(vec![0.4, 0.5, 0.6]),
+                hexatic_order: None,
+                class: vec![Classes::P2GG, Classes::P2, Classes::Liquid],
+                area: Some(vec![1., 1.5, 2.]),
+                metrics: Vec::new(),
+            },
+        ];
+
+        let mut filename = std::env::temp_dir();
+        filename.push(format!("results-roundtrip-{}.csv", std::process::id()));
+        let mut writer = csv::Writer::from_path(&filename)?;
+        for result in results.iter().cloned() {
+            let rows: Vec<Row> = result.into();
+            for row in rows {
+                writer.serialize(row)?;
+            }
+        }
+        writer.flush()?;
+
+        let loaded = load_results(&filename)?;
+        std::fs::remove_file(&filename)?;
+        assert_eq!(loaded, results);
+        Ok(())
+    }
+}
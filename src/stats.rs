@@ -0,0 +1,303 @@
+//
+// stats.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Utilities for summarising the per-particle values computed from a frame
+
+/// Find the bin a value belongs to, returning None when it is outside the range
+///
+/// The bins are half open intervals [min, max) with the exception of the final bin, which also
+/// includes the value max. This means the values min and max are both counted.
+///
+fn bin_index(value: f32, min: f32, max: f32, n_bins: usize) -> Option<usize> {
+    if !(min..=max).contains(&value) {
+        return None;
+    }
+    let index = ((value - min) / (max - min) * n_bins as f32) as usize;
+    Some(index.min(n_bins - 1))
+}
+
+/// Count the values within each of `n_bins` evenly spaced bins between `min` and `max`
+///
+/// Values outside the range [min, max], including NaN values, are dropped rather than being
+/// clamped into the end bins, so the sum of the counts can be less than the number of values.
+///
+pub fn histogram(values: &[f32], min: f32, max: f32, n_bins: usize) -> Vec<u64> {
+    let mut counts = vec![0; n_bins];
+    if n_bins == 0 {
+        return counts;
+    }
+    for index in values
+        .iter()
+        .filter_map(|&v| bin_index(v, min, max, n_bins))
+    {
+        counts[index] += 1;
+    }
+    counts
+}
+
+/// The histogram of values normalised such that the bins sum to 1
+///
+/// This uses the same binning and out of range policy as [`histogram`], with the normalisation
+/// performed using the number of values which fall within the range. Where there are no values in
+/// the range, all the bins are zero.
+///
+pub fn histogram_normalised(values: &[f32], min: f32, max: f32, n_bins: usize) -> Vec<f32> {
+    let counts = histogram(values, min, max, n_bins);
+    let total: u64 = counts.iter().sum();
+    if total == 0 {
+        return vec![0.; n_bins];
+    }
+    counts
+        .into_iter()
+        .map(|c| c as f32 / total as f32)
+        .collect()
+}
+
+/// The value below which a fraction `q` of the values fall
+///
+/// This linearly interpolates between the sorted values, so a `q` of 0 and 1 give the minimum and
+/// maximum, while 0.5 gives the median. Values of `q` outside [0, 1] are clamped to that range.
+/// NaN values are ignored, like with [`histogram`], and the result is NaN when there are no other
+/// values.
+///
+pub fn quantile(values: &[f32], q: f32) -> f32 {
+    let mut sorted: Vec<f32> = values.iter().copied().filter(|v| !v.is_nan()).collect();
+    if sorted.is_empty() {
+        return f32::NAN;
+    }
+    sorted.sort_by(|a, b| a.partial_cmp(b).expect("NaN values are removed"));
+    let position = q.clamp(0., 1.) * (sorted.len() - 1) as f32;
+    let lower = position.floor() as usize;
+    let upper = position.ceil() as usize;
+    let fraction = position - lower as f32;
+    sorted[lower] + (sorted[upper] - sorted[lower]) * fraction
+}
+
+/// The mean value of each particle over a sliding window of frames
+///
+/// Each item of `series` contains the values for every particle in a single frame. The result
+/// contains the mean over each window of `window` consecutive frames, so there are
+/// `series.len() - window + 1` frames in the result. Where the window is zero or larger than the
+/// number of frames the result is empty.
+///
+/// # Panics
+///
+/// When the frames have a different number of particles.
+///
+pub fn windowed_average(series: &[Vec<f32>], window: usize) -> Vec<Vec<f32>> {
+    if window == 0 || window > series.len() {
+        return Vec::new();
+    }
+    series
+        .windows(window)
+        .map(|frames| {
+            let mut mean = vec![0.; frames[0].len()];
+            for frame in frames {
+                assert_eq!(
+                    frame.len(),
+                    mean.len(),
+                    "Each frame needs the same number of particles"
+                );
+                for (m, v) in mean.iter_mut().zip(frame) {
+                    *m += v / window as f32;
+                }
+            }
+            mean
+        })
+        .collect()
+}
+
+/// The mean and variance of each particle, updated one frame at a time
+///
+/// This uses Welford's algorithm, so the statistics of a long trajectory are found without
+/// keeping the values of every frame in memory.
+///
+/// ```
+/// use trajedy::stats::RunningStats;
+///
+/// let mut stats = RunningStats::default();
+/// stats.update(&[1., 2.]);
+/// stats.update(&[3., 2.]);
+/// assert_eq!(stats.mean(), vec![2., 2.]);
+/// assert_eq!(stats.variance(), vec![1., 0.]);
+/// ```
+///
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RunningStats {
+    count: u64,
+    mean: Vec<f64>,
+    m2: Vec<f64>,
+}
+
+impl RunningStats {
+    /// Include the values of every particle from another frame
+    ///
+    /// # Panics
+    ///
+    /// When the frame has a different number of particles to the previous frames.
+    ///
+    pub fn update(&mut self, values: &[f32]) {
+        if self.count == 0 {
+            self.mean = vec![0.; values.len()];
+            self.m2 = vec![0.; values.len()];
+        }
+        assert_eq!(
+            values.len(),
+            self.mean.len(),
+            "Each frame needs the same number of particles"
+        );
+        self.count += 1;
+        for ((mean, m2), &value) in self.mean.iter_mut().zip(self.m2.iter_mut()).zip(values) {
+            let value = f64::from(value);
+            let delta = value - *mean;
+            *mean += delta / self.count as f64;
+            *m2 += delta * (value - *mean);
+        }
+    }
+
+    /// The number of frames which have been included
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// The mean value of each particle
+    pub fn mean(&self) -> Vec<f32> {
+        self.mean.iter().map(|&m| m as f32).collect()
+    }
+
+    /// The population variance of each particle, which is zero after a single frame
+    pub fn variance(&self) -> Vec<f32> {
+        self.m2
+            .iter()
+            .map(|&m2| (m2 / self.count as f64) as f32)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn histogram_empty() {
+        assert_eq!(histogram(&[], 0., 1., 4), vec![0; 4]);
+        assert_eq!(histogram_normalised(&[], 0., 1., 4), vec![0.; 4]);
+    }
+
+    #[test]
+    fn histogram_bin_edges() {
+        let values = [0., 0.25, 0.5, 0.75, 1.];
+        assert_eq!(histogram(&values, 0., 1., 4), vec![1, 1, 1, 2]);
+    }
+
+    #[test]
+    fn histogram_out_of_range() {
+        let values = [-0.1, 0.1, 1.1, f32::NAN];
+        assert_eq!(histogram(&values, 0., 1., 2), vec![1, 0]);
+    }
+
+    #[test]
+    fn histogram_normalised_sum() {
+        let values = [0.1, 0.2, 0.6, 0.9, 2.];
+        let hist = histogram_normalised(&values, 0., 1., 2);
+        assert_abs_diff_eq!(hist[0], 0.5);
+        assert_abs_diff_eq!(hist[1], 0.5);
+    }
+
+    #[test]
+    fn quantile_sorted() {
+        let values = [1., 2., 3., 4., 10.];
+        assert_eq!(quantile(&values, 0.), 1.);
+        assert_eq!(quantile(&values, 0.5), 3.);
+        assert_eq!(quantile(&values, 1.), 10.);
+        assert_abs_diff_eq!(quantile(&values, 0.9), 7.6, epsilon = 1e-5);
+        // The order of the values doesn't matter
+        assert_eq!(quantile(&[10., 4., 1., 3., 2.], 0.5), 3.);
+        // Interpolating between the two central values
+        assert_eq!(quantile(&[1., 2., 3., 4.], 0.5), 2.5);
+    }
+
+    #[test]
+    fn quantile_edge_cases() {
+        assert!(quantile(&[], 0.5).is_nan());
+        assert!(quantile(&[f32::NAN], 0.5).is_nan());
+        assert_eq!(quantile(&[1., f32::NAN, 3.], 0.5), 2.);
+        assert_eq!(quantile(&[1., 2.], -1.), 1.);
+        assert_eq!(quantile(&[1., 2.], 2.), 2.);
+        assert_eq!(quantile(&[5.], 0.3), 5.);
+    }
+
+    #[test]
+    fn windowed_average_oscillating() {
+        // Each particle oscillates with a period of 2 frames, with a different phase
+        let series: Vec<Vec<f32>> = (0..10)
+            .map(|i| {
+                let sign = if i % 2 == 0 { 1. } else { -1. };
+                vec![1. + sign, 2. - sign]
+            })
+            .collect();
+        let averaged = windowed_average(&series, 2);
+        assert_eq!(averaged.len(), 9);
+        for frame in averaged {
+            assert_abs_diff_eq!(frame[0], 1.);
+            assert_abs_diff_eq!(frame[1], 2.);
+        }
+    }
+
+    #[test]
+    fn windowed_average_window_size() {
+        let series = vec![vec![1.], vec![2.], vec![3.]];
+        assert_eq!(windowed_average(&series, 1), series);
+        assert_eq!(windowed_average(&series, 3), vec![vec![2.]]);
+        assert!(windowed_average(&series, 0).is_empty());
+        assert!(windowed_average(&series, 4).is_empty());
+    }
+
+    #[test]
+    fn running_stats_batch() {
+        let series: Vec<Vec<f32>> = (0..50)
+            .map(|i| {
+                let i = i as f32;
+                vec![(i * 0.37).sin() + 1000., i * i * 0.01]
+            })
+            .collect();
+        let mut stats = RunningStats::default();
+        for frame in series.iter() {
+            stats.update(frame);
+        }
+        assert_eq!(stats.count(), 50);
+
+        for (particle, (mean, variance)) in
+            stats.mean().into_iter().zip(stats.variance()).enumerate()
+        {
+            let values: Vec<f64> = series.iter().map(|f| f64::from(f[particle])).collect();
+            let expected_mean = values.iter().sum::<f64>() / values.len() as f64;
+            let expected_variance = values
+                .iter()
+                .map(|v| (v - expected_mean).powi(2))
+                .sum::<f64>()
+                / values.len() as f64;
+            assert_abs_diff_eq!(mean as f64, expected_mean, epsilon = 1e-4);
+            assert_abs_diff_eq!(variance as f64, expected_variance, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn running_stats_empty() {
+        let stats = RunningStats::default();
+        assert!(stats.mean().is_empty());
+        assert!(stats.variance().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "same number of particles")]
+    fn running_stats_mismatch() {
+        let mut stats = RunningStats::default();
+        stats.update(&[1., 2.]);
+        stats.update(&[1.]);
+    }
+}
@@ -0,0 +1,237 @@
+//
+// steinhardt.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Bond orientational order parameters using spherical harmonics
+//!
+//! These follow the definitions of Steinhardt, Nelson and Ronchetti for $q_l$, along with the
+//! neighbour averaged $\bar{q}_l$ of Lechner and Dellago.
+
+use crate::frame::Frame;
+use nalgebra::{Complex, Vector3};
+use std::f64::consts::PI;
+
+/// The associated Legendre polynomials $P_l^m(x)$ for all $m$ in $0..=l$
+///
+/// This includes the Condon-Shortley phase, using the standard recurrence relations in $l$ for
+/// each value of $m$.
+///
+fn associated_legendre(l: usize, x: f64) -> Vec<f64> {
+    let sin_theta = (1. - x * x).max(0.).sqrt();
+    (0..=l)
+        .map(|m| {
+            // P_m^m = (-1)^m (2m-1)!! (1-x^2)^{m/2}
+            let mut p_mm = 1.;
+            for i in 0..m {
+                p_mm *= -((2 * i + 1) as f64) * sin_theta;
+            }
+            if l == m {
+                return p_mm;
+            }
+            let mut p_prev = p_mm;
+            let mut p_curr = x * (2 * m + 1) as f64 * p_mm;
+            for ll in (m + 2)..=l {
+                let p_next = ((2 * ll - 1) as f64 * x * p_curr - (ll + m - 1) as f64 * p_prev)
+                    / (ll - m) as f64;
+                p_prev = p_curr;
+                p_curr = p_next;
+            }
+            p_curr
+        })
+        .collect()
+}
+
+/// The spherical harmonics $Y_l^m$ of a vector for all $m$ in $0..=l$
+///
+/// The values for negative $m$ are not computed, since $Y_l^{-m} = (-1)^m \overline{Y_l^m}$ which
+/// has the same magnitude.
+///
+fn spherical_harmonics(l: usize, vector: &Vector3<f32>) -> Vec<Complex<f64>> {
+    let vector = vector.cast::<f64>();
+    let cos_theta = vector.z / vector.norm();
+    let phi = vector.y.atan2(vector.x);
+    let legendre = associated_legendre(l, cos_theta);
+
+    let mut factorial_ratio = 1.;
+    (0..=l)
+        .map(|m| {
+            // This is (l-m)! / (l+m)!, updated incrementally for each m
+            if m > 0 {
+                factorial_ratio /= ((l + m) * (l - m + 1)) as f64;
+            }
+            let norm = ((2 * l + 1) as f64 / (4. * PI) * factorial_ratio).sqrt();
+            let magnitude = norm * legendre[m];
+            let angle = m as f64 * phi;
+            Complex::new(magnitude * angle.cos(), magnitude * angle.sin())
+        })
+        .collect()
+}
+
+/// The $q_{lm}$ values of each particle, the average spherical harmonics of the neighbour bonds
+fn bond_harmonics(frame: &Frame, l: usize, num_neighbours: usize) -> Vec<Vec<Complex<f64>>> {
+    (0..frame.len())
+        .map(|index| {
+            let mut q_lm = vec![Complex::new(0., 0.); l + 1];
+            let mut count = 0;
+            for neighbour in frame.particle_neighbours_n(index, num_neighbours) {
+                let harmonics = spherical_harmonics(l, &frame.neighbour_vector(index, neighbour));
+                for (q, y) in q_lm.iter_mut().zip(harmonics) {
+                    *q += y;
+                }
+                count += 1;
+            }
+            // Average over the neighbours found, which can be fewer than requested
+            for q in q_lm.iter_mut() {
+                *q /= count.max(1) as f64;
+            }
+            q_lm
+        })
+        .collect()
+}
+
+/// The rotationally invariant combination of the $q_{lm}$ values
+///
+/// $$ q_l = \sqrt{\frac{4\pi}{2l+1} \sum_{m=-l}^{l} |q_{lm}|^2} $$
+///
+fn invariant(l: usize, q_lm: &[Complex<f64>]) -> f32 {
+    // The negative values of m have the same magnitude as the positive values
+    let sum: f64 = q_lm[0].norm_sqr() + 2. * q_lm[1..].iter().map(|q| q.norm_sqr()).sum::<f64>();
+    (4. * PI / (2 * l + 1) as f64 * sum).sqrt() as f32
+}
+
+/// Compute the Steinhardt bond order parameter $q_l$ for every particle in a configuration
+///
+/// The bonds are the vectors to the `num_neighbours` nearest particles.
+///
+pub fn steinhardt_order(frame: &Frame, l: usize, num_neighbours: usize) -> Vec<f32> {
+    bond_harmonics(frame, l, num_neighbours)
+        .iter()
+        .map(|q_lm| invariant(l, q_lm))
+        .collect()
+}
+
+/// Compute the averaged bond order parameter $\bar{q}_l$ for every particle in a configuration
+///
+/// This averages the $q_{lm}$ values of each particle with those of its `num_neighbours` nearest
+/// neighbours before taking the rotational invariant, which includes information from the second
+/// shell of neighbours, giving a clearer separation of crystal structures.
+///
+pub fn averaged_steinhardt_order(frame: &Frame, l: usize, num_neighbours: usize) -> Vec<f32> {
+    let q_lm = bond_harmonics(frame, l, num_neighbours);
+    (0..frame.len())
+        .map(|index| {
+            let mut average = q_lm[index].clone();
+            let mut count = 0;
+            for neighbour in frame.particle_neighbours_n(index, num_neighbours) {
+                for (a, q) in average.iter_mut().zip(q_lm[neighbour].iter()) {
+                    *a += q;
+                }
+                count += 1;
+            }
+            // The average includes the particle itself
+            for a in average.iter_mut() {
+                *a /= (count + 1) as f64;
+            }
+            invariant(l, &average)
+        })
+        .collect()
+}
+
+/// The real part of the sum over all $m$ of $q_{lm}(i) \overline{q_{lm}(j)}$
+///
+/// Like [`invariant`], the terms with negative $m$ are the complex conjugates of those with
+/// positive $m$, so have the same real part.
+///
+fn harmonic_dot(a: &[Complex<f64>], b: &[Complex<f64>]) -> f64 {
+    let product = |(x, y): (&Complex<f64>, &Complex<f64>)| (x * y.conj()).re;
+    product((&a[0], &b[0])) + 2. * a[1..].iter().zip(b[1..].iter()).map(product).sum::<f64>()
+}
+
+/// Count the neighbours of each particle which are connected by the ten Wolde criterion
+///
+/// The bond between a particle $i$ and each of its `num_neighbours` nearest neighbours $j$ is
+/// connected when the normalised product of their $q_{6m}$ vectors,
+///
+/// $$ s_{ij} = \frac{\sum_m q_{6m}(i) \overline{q_{6m}(j)}}{|q_6(i)| |q_6(j)|} $$
+///
+/// is greater than `threshold`. The particles of a crystal have most of their neighbours
+/// connected, while the local order of a liquid is uncorrelated between neighbours, so a typical
+/// threshold is 0.7, with particles having more than about half their neighbours connected
+/// being crystalline.
+///
+pub fn crystalline_neighbours(frame: &Frame, num_neighbours: usize, threshold: f32) -> Vec<usize> {
+    let q_lm = bond_harmonics(frame, 6, num_neighbours);
+    let norms: Vec<f64> = q_lm.iter().map(|q| harmonic_dot(q, q).sqrt()).collect();
+    (0..frame.len())
+        .map(|index| {
+            frame
+                .particle_neighbours_n(index, num_neighbours)
+                .filter(|&neighbour| {
+                    let dot = harmonic_dot(&q_lm[index], &q_lm[neighbour]);
+                    dot / (norms[index] * norms[neighbour]) > f64::from(threshold)
+                })
+                .count()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{displacement, fcc, hcp};
+    use approx::assert_abs_diff_eq;
+    use gsd::GSDFrame;
+
+    #[test]
+    fn steinhardt_perfect_crystals() {
+        for q6 in steinhardt_order(&fcc(0.), 6, 12) {
+            assert_abs_diff_eq!(q6, 0.5745, epsilon = 1e-3);
+        }
+        for q6 in steinhardt_order(&hcp(0.), 6, 12) {
+            assert_abs_diff_eq!(q6, 0.4848, epsilon = 1e-3);
+        }
+        for q4 in steinhardt_order(&fcc(0.), 4, 12) {
+            assert_abs_diff_eq!(q4, 0.1909, epsilon = 1e-3);
+        }
+    }
+
+    #[test]
+    fn crystalline_neighbours_fcc_liquid() {
+        // With a little noise every neighbour of the crystal is connected
+        for count in crystalline_neighbours(&fcc(0.05), 12, 0.7) {
+            assert_eq!(count, 12);
+        }
+
+        // Random positions within the same volume as the crystal
+        let length = 4. * 2_f32.sqrt();
+        let position: Vec<[f32; 3]> = (0..256)
+            .map(|index| {
+                let d = displacement(index, length);
+                [d[0].abs(), d[1].abs(), d[2].abs()]
+            })
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let liquid: Frame =
+            GSDFrame::from_arrays(position, orientation, [length, length, length, 0., 0., 0.])
+                .into();
+        let counts = crystalline_neighbours(&liquid, 12, 0.7);
+        let mean = counts.iter().sum::<usize>() as f32 / counts.len() as f32;
+        assert!(mean < 3., "Mean connections of the liquid {}", mean);
+    }
+
+    #[test]
+    fn averaged_steinhardt_separates() {
+        let max = |values: Vec<f32>| values.into_iter().fold(f32::MIN, f32::max);
+        let min = |values: Vec<f32>| values.into_iter().fold(f32::MAX, f32::min);
+
+        let (fcc, hcp) = (fcc(0.12), hcp(0.12));
+        // The distributions of q6 overlap with this much noise
+        assert!(max(steinhardt_order(&hcp, 6, 12)) > min(steinhardt_order(&fcc, 6, 12)));
+        assert!(
+            max(averaged_steinhardt_order(&hcp, 6, 12))
+                < min(averaged_steinhardt_order(&fcc, 6, 12))
+        );
+    }
+}
@@ -0,0 +1,91 @@
+//
+// strain.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Measures of the local deformation between two frames
+
+use crate::frame::Frame;
+use anyhow::{bail, Error};
+use nalgebra::Matrix3;
+
+/// The tolerance for the singular values when inverting the neighbour covariance matrix
+const PSEUDO_INVERSE_EPS: f32 = 1e-6;
+
+/// Compute the non-affine displacement $D^2_{min}$ of each particle between two frames
+///
+/// For each particle, the `num_neighbours` nearest neighbours in the reference frame define the
+/// local environment. The affine transformation $J$ which best maps the reference neighbour
+/// vectors $d^0_j$ onto the current vectors $d_j$ is found using least squares, with
+///
+/// $$ D^2_{min} = \sum_j | d_j - J d^0_j |^2 $$
+///
+/// being the remaining displacement. A pseudo-inverse is used in finding $J$, which means this
+/// handles 2D configurations where all the particles lie in a plane.
+///
+pub fn d2min(reference: &Frame, current: &Frame, num_neighbours: usize) -> Result<Vec<f32>, Error> {
+    if reference.len() != current.len() {
+        bail!(
+            "The reference frame has {} particles, while the current frame has {}",
+            reference.len(),
+            current.len()
+        );
+    }
+
+    (0..reference.len())
+        .map(|index| {
+            let neighbours: Vec<usize> = reference
+                .particle_neighbours_n(index, num_neighbours)
+                .collect();
+            let pairs: Vec<_> = neighbours
+                .iter()
+                .map(|&n| {
+                    (
+                        reference.neighbour_vector(index, n),
+                        current.neighbour_vector(index, n),
+                    )
+                })
+                .collect();
+
+            let mut x = Matrix3::zeros();
+            let mut y = Matrix3::zeros();
+            for (d0, d) in pairs.iter() {
+                x += d * d0.transpose();
+                y += d0 * d0.transpose();
+            }
+            let transform = x * y.pseudo_inverse(PSEUDO_INVERSE_EPS).map_err(Error::msg)?;
+
+            Ok(pairs
+                .iter()
+                .map(|(d0, d)| (d - transform * d0).norm_squared())
+                .sum())
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::sheared_lattice;
+    use approx::assert_abs_diff_eq;
+
+    #[test]
+    fn affine_strain() -> Result<(), Error> {
+        let reference = sheared_lattice(10, 0.);
+        let current = sheared_lattice(10, 0.1);
+        let values = d2min(&reference, &current, 9)?;
+        assert_eq!(values.len(), 100);
+        for value in values {
+            assert_abs_diff_eq!(value, 0., epsilon = 1e-4);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn particle_count_mismatch() {
+        let reference = sheared_lattice(10, 0.);
+        let current = sheared_lattice(8, 0.);
+        assert!(d2min(&reference, &current, 6).is_err());
+    }
+}
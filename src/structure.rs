@@ -0,0 +1,362 @@
+//
+// structure.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Measures of the structure of a configuration
+//!
+//! This includes the structure factor for comparison with scattering experiments, along with
+//! measures of the local geometry around each particle.
+
+use crate::cell::SimulationCell;
+use crate::frame::Frame;
+use crate::stats::{histogram, histogram_normalised};
+use anyhow::{bail, Error};
+use itertools::iproduct;
+use nalgebra::Vector3;
+use std::f32::consts::PI;
+
+/// The wave vectors of the reciprocal lattice with a magnitude within `tolerance` of `q`
+fn wave_vectors(
+    reciprocal: &[Vector3<f32>; 3],
+    lengths: &[f32; 3],
+    q: f32,
+    tolerance: f32,
+) -> Vec<Vector3<f32>> {
+    // The largest multiple of each reciprocal vector which could have the magnitude q
+    let limit: Vec<i32> = lengths
+        .iter()
+        .map(|l| ((q + tolerance) * l / (2. * PI)).ceil() as i32)
+        .collect();
+    iproduct!(
+        -limit[0]..=limit[0],
+        -limit[1]..=limit[1],
+        -limit[2]..=limit[2]
+    )
+    .filter(|&n| n != (0, 0, 0))
+    .map(|(x, y, z)| reciprocal[0] * x as f32 + reciprocal[1] * y as f32 + reciprocal[2] * z as f32)
+    .filter(|g| (g.norm() - q).abs() <= tolerance)
+    .collect()
+}
+
+/// Compute the static structure factor $S(q)$ for each of the magnitudes `q_values`
+///
+/// The structure factor is only defined for the wave vectors of the reciprocal lattice of the
+/// simulation cell, so for each magnitude $q$ this averages
+///
+/// $$ S(\mathbf{q}) = \frac{1}{N} \left| \sum_j \exp{i \mathbf{q} \cdot \mathbf{r}_j} \right|^2 $$
+///
+/// over the wave vectors with a magnitude within half the smallest reciprocal lattice spacing of
+/// $q$. For a 2D configuration, as given by [`Frame::effective_dimensions`], only the wave
+/// vectors in the xy plane are used, giving a circular rather than spherical average. Where there
+/// are no wave vectors with a magnitude close to $q$ the value is NaN. This returns an error when
+/// the simulation cell is degenerate, since it doesn't have a reciprocal lattice.
+///
+pub fn structure_factor(frame: &Frame, q_values: &[f32]) -> Result<Vec<f32>, Error> {
+    let cell = SimulationCell::from(frame.simulation_cell);
+    let two_dimensional = frame.effective_dimensions() == 2;
+
+    let inverse = match cell.to_matrix().try_inverse() {
+        Some(inverse) => inverse,
+        None => bail!(
+            "The simulation cell {:?} is not invertible",
+            frame.simulation_cell
+        ),
+    };
+    let reciprocal_matrix = inverse.transpose() * 2. * PI;
+    let reciprocal = [
+        reciprocal_matrix.column(0).into_owned(),
+        reciprocal_matrix.column(1).into_owned(),
+        reciprocal_matrix.column(2).into_owned(),
+    ];
+    let mut lengths = cell.lengths();
+    if two_dimensional {
+        // No multiples of the z reciprocal vector are used
+        lengths[2] = 0.;
+    }
+    let used = if two_dimensional { 2 } else { 3 };
+    let tolerance = reciprocal[..used]
+        .iter()
+        .map(|b| b.norm())
+        .fold(f32::MAX, f32::min)
+        / 2.;
+
+    Ok(q_values
+        .iter()
+        .map(|&q| {
+            let vectors = wave_vectors(&reciprocal, &lengths, q, tolerance);
+            let total: f32 = vectors
+                .iter()
+                .map(|g| {
+                    let (re, im) = frame
+                        .position
+                        .iter()
+                        .map(|r| g.dot(&r.coords))
+                        .fold((0., 0.), |(re, im), phase| {
+                            (re + phase.cos(), im + phase.sin())
+                        });
+                    (re * re + im * im) / frame.len() as f32
+                })
+                .sum();
+            total / vectors.len() as f32
+        })
+        .collect())
+}
+
+/// Compute the distribution of the angles between the bonds to the neighbours of each particle
+///
+/// For every particle, this finds the angle between each pair of the vectors to its
+/// `num_neighbours` nearest neighbours, with the angles from all particles combined into a
+/// histogram of `n_bins` evenly spaced bins over the range $[0, \pi]$. The histogram is
+/// normalised such that the bins sum to 1.
+///
+pub fn bond_angle_distribution(frame: &Frame, num_neighbours: usize, n_bins: usize) -> Vec<f32> {
+    let angles: Vec<f32> = (0..frame.len())
+        .flat_map(|index| {
+            let bonds = frame.neighbour_vectors_n(index, num_neighbours);
+            (0..bonds.len())
+                .flat_map(|i| ((i + 1)..bonds.len()).map(move |j| (i, j)))
+                .map(|(i, j)| bonds[i].angle(&bonds[j]))
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    histogram_normalised(&angles, 0., PI, n_bins)
+}
+
+/// Compute the correlation of the orientations of pairs of particles as a function of distance
+///
+/// For each pair of particles separated by less than `r_max`, taking the periodic boundary into
+/// account, this finds $\cos(2 \Delta\theta)$ where $\Delta\theta$ is the angle between the
+/// orientations of the particles. The values are averaged within `n_bins` evenly spaced bins of
+/// the distance over the range $[0, r_{max})$, so aligned particles have a value of 1 while
+/// uncorrelated particles average to 0. Where there are no pairs within a bin the value is NaN.
+///
+pub fn orientation_correlation(frame: &Frame, r_max: f32, n_bins: usize) -> Vec<f32> {
+    let mut total = vec![0.; n_bins];
+    let mut counts = vec![0_u64; n_bins];
+    for (index, neighbours) in frame.neighbours_cutoff(r_max).enumerate() {
+        for neighbour in neighbours {
+            let distance = frame.neighbour_vector(index, neighbour).norm();
+            let bin = (distance / r_max * n_bins as f32) as usize;
+            if bin >= n_bins {
+                continue;
+            }
+            let angle = frame.orientation[index].angle_to(&frame.orientation[neighbour]);
+            total[bin] += (2. * angle).cos();
+            counts[bin] += 1;
+        }
+    }
+    total
+        .into_iter()
+        .zip(counts)
+        .map(|(t, c)| t / c as f32)
+        .collect()
+}
+
+/// Compute the radial distribution function separated into the contribution of each neighbour
+///
+/// The neighbours of each particle are ordered by distance, with the distance to the $k$th
+/// nearest neighbour contributing to the $k$th shell, for the first `max_shell` neighbours. The
+/// distances of each shell are binned into `n_bins` evenly spaced bins over the range
+/// $[0, r_{max}]$, which are normalised by the number of particles, the density and the area (2D)
+/// or volume (3D) of each bin, so summing the shells gives the radial distribution function
+/// $g(r)$ up to the distance where every neighbour is within one of the shells.
+///
+pub fn shell_resolved_rdf(
+    frame: &Frame,
+    max_shell: usize,
+    r_max: f32,
+    n_bins: usize,
+) -> Vec<Vec<f32>> {
+    let mut distances = vec![Vec::with_capacity(frame.len()); max_shell];
+    for index in 0..frame.len() {
+        for (shell, vector) in frame
+            .neighbour_vectors_n(index, max_shell)
+            .into_iter()
+            .enumerate()
+        {
+            distances[shell].push(vector.norm());
+        }
+    }
+
+    let cell = SimulationCell::from(frame.simulation_cell);
+    let two_dimensional = frame.effective_dimensions() == 2;
+    let density = if two_dimensional {
+        frame.len() as f32 / (cell.lx * cell.ly)
+    } else {
+        frame.len() as f32 / cell.volume()
+    };
+    let width = r_max / n_bins as f32;
+    // The area or volume of the shell between the edges of each bin
+    let bin_size = |bin: usize| {
+        let (inner, outer) = (bin as f32 * width, (bin + 1) as f32 * width);
+        if two_dimensional {
+            PI * (outer.powi(2) - inner.powi(2))
+        } else {
+            4. / 3. * PI * (outer.powi(3) - inner.powi(3))
+        }
+    };
+
+    distances
+        .iter()
+        .map(|shell| {
+            histogram(shell, 0., r_max, n_bins)
+                .into_iter()
+                .enumerate()
+                .map(|(bin, count)| count as f32 / (frame.len() as f32 * density * bin_size(bin)))
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{square_lattice, triangular_lattice};
+    use approx::assert_abs_diff_eq;
+    use gsd::GSDFrame;
+
+    #[test]
+    fn bond_angle_hexagonal_peaks() {
+        // Bins of 9 degrees, so 60 and 120 degrees are within the bins 6 and 13
+        let distribution = bond_angle_distribution(&triangular_lattice(8, 0.), 6, 20);
+        assert_eq!(distribution.len(), 20);
+        // Of the 15 pairs of neighbours, 6 are at 60, 6 are at 120 and 3 are at 180 degrees
+        for (bin, value) in distribution.into_iter().enumerate() {
+            let expected = match bin {
+                6 | 13 => 0.4,
+                19 => 0.2,
+                _ => 0.,
+            };
+            assert_abs_diff_eq!(value, expected, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn shell_resolved_rdf_dimers() {
+        // Rows of dimers with a bond length of 1, with a gap of 1.5 between the dimers
+        let position: Vec<[f32; 3]> = (0..4)
+            .flat_map(|y| (0..8).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let offset = if x % 2 == 0 { 0. } else { 1. };
+                [(x / 2) as f32 * 2.5 + offset, y as f32 * 3., 0.]
+            })
+            .collect();
+        let orientation = vec![[1., 0., 0., 0.]; position.len()];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [10., 12., 1., 0., 0., 0.]).into();
+
+        let rdf = shell_resolved_rdf(&frame, 2, 2., 5);
+        assert_eq!(rdf.len(), 2);
+        let peak = |values: &[f32]| {
+            values
+                .iter()
+                .enumerate()
+                .max_by(|a, b| a.1.total_cmp(b.1))
+                .map(|(index, _)| index)
+        };
+        // The bins have a width of 0.4, keeping the distances away from the edges of the bins
+        assert_eq!(peak(&rdf[0]), Some(2));
+        assert_eq!(peak(&rdf[1]), Some(3));
+        // Each shell only contains a single distance
+        assert_eq!(rdf[0].iter().filter(|&&v| v > 0.).count(), 1);
+        assert_eq!(rdf[1].iter().filter(|&&v| v > 0.).count(), 1);
+    }
+
+    #[test]
+    fn structure_factor_bragg_peak() -> Result<(), Error> {
+        let frame = square_lattice(8);
+        // Before the first peak the contributions from each particle cancel out
+        let s = structure_factor(&frame, &[2. * PI * 3. / 8.])?;
+        assert_abs_diff_eq!(s[0], 0., epsilon = 1e-3);
+
+        // The first peak is at the magnitude of the reciprocal lattice vectors of the crystal
+        let q_values: Vec<f32> = (4..=12).map(|k| 2. * PI * k as f32 / 8.).collect();
+        let s = structure_factor(&frame, &q_values)?;
+        let peak = s
+            .iter()
+            .enumerate()
+            .max_by(|a, b| a.1.total_cmp(b.1))
+            .map(|(index, _)| index);
+        assert_eq!(peak, Some(4));
+        assert!(s[4] > 1.);
+        Ok(())
+    }
+
+    #[test]
+    fn structure_factor_dimensions() -> Result<(), Error> {
+        let q_values = [2. * PI / 8., 2. * PI * 1.5 / 8.];
+        let expected = structure_factor(&square_lattice(8), &q_values)?;
+
+        // The dimensions in the file determine the wave vectors, even when the z positions vary
+        let mut frame = square_lattice(8);
+        frame.dimensions = Some(2);
+        frame.position[3].z = 0.1;
+        let s = structure_factor(&frame, &q_values)?;
+        assert_abs_diff_eq!(s[0], expected[0], epsilon = 1e-3);
+        assert_abs_diff_eq!(s[1], expected[1], epsilon = 1e-3);
+
+        // A single layer of particles within a cell which is periodic in z is three dimensional,
+        // with the wave vectors along z giving a large contribution
+        let mut frame = square_lattice(8);
+        frame.simulation_cell[2] = 8.;
+        let s = structure_factor(&frame, &q_values)?;
+        assert!(s[0] > expected[0]);
+        Ok(())
+    }
+
+    #[test]
+    fn structure_factor_no_wave_vectors() -> Result<(), Error> {
+        let frame = square_lattice(4);
+        assert!(structure_factor(&frame, &[0.1])?[0].is_nan());
+        Ok(())
+    }
+
+    #[test]
+    fn structure_factor_degenerate_cell() {
+        let position = vec![[0., 0., 0.], [1., 1., 0.]];
+        let orientation = vec![[1., 0., 0., 0.]; 2];
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [4., 4., 0., 0., 0., 0.]).into();
+        assert!(structure_factor(&frame, &[1.]).is_err());
+    }
+
+    #[test]
+    fn orientation_correlation_aligned() {
+        let frame = square_lattice(8);
+        let correlation = orientation_correlation(&frame, 3., 6);
+        assert_eq!(correlation.len(), 6);
+        // The lattice has no pairs closer than 1, or with a distance between 1.5 and 2
+        for &bin in [0, 1, 3].iter() {
+            assert!(correlation[bin].is_nan());
+        }
+        for &bin in [2, 4, 5].iter() {
+            assert_abs_diff_eq!(correlation[bin], 1., epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn orientation_correlation_alternating() {
+        // Particles on alternating sites are rotated by 90 degrees around the z axis
+        let position: Vec<[f32; 3]> = (0..8)
+            .flat_map(|y| (0..8).map(move |x| [x as f32, y as f32, 0.]))
+            .collect();
+        let (s, c) = (PI / 4.).sin_cos();
+        let orientation = (0..8)
+            .flat_map(|y| (0..8).map(move |x| (x + y) % 2))
+            .map(|parity| {
+                if parity == 0 {
+                    [1., 0., 0., 0.]
+                } else {
+                    [c, 0., 0., s]
+                }
+            })
+            .collect();
+        let frame: Frame =
+            GSDFrame::from_arrays(position, orientation, [8., 8., 1., 0., 0., 0.]).into();
+        let correlation = orientation_correlation(&frame, 1.2, 6);
+        // The nearest neighbours at a distance of 1 are all rotated relative to each particle
+        assert_abs_diff_eq!(correlation[5], -1., epsilon = 1e-5);
+    }
+}
@@ -0,0 +1,135 @@
+//
+// test_utils.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Frames with known structures shared between the tests
+//!
+//! The benchmarks also include this file, so it only refers to the crate through the `frame`
+//! module, which the benchmarks import at their root.
+//!
+
+use crate::frame::Frame;
+use gsd::GSDFrame;
+
+/// A small deterministic displacement for each particle, standing in for thermal noise
+pub fn displacement(index: usize, amplitude: f32) -> [f32; 3] {
+    let noise = |seed: f32| ((seed * 12.9898).sin() * 43758.547).fract();
+    let i = index as f32;
+    [
+        amplitude * noise(i + 0.1),
+        amplitude * noise(i + 0.4),
+        amplitude * noise(i + 0.7),
+    ]
+}
+
+/// A square lattice with unit spacing of `n` x `n` particles centred on the origin
+pub fn square_lattice(n: usize) -> Frame {
+    sheared_lattice(n, 0.)
+}
+
+/// A square lattice with an applied shear strain, with the cell sheared to match
+pub fn sheared_lattice(n: usize, shear: f32) -> Frame {
+    let position: Vec<[f32; 3]> = (0..n)
+        .flat_map(|y| {
+            (0..n).map(move |x| {
+                let (x, y) = (x as f32 - (n / 2) as f32, y as f32 - (n / 2) as f32);
+                [x + shear * y, y, 0.]
+            })
+        })
+        .collect();
+    let orientation = vec![[1., 0., 0., 0.]; position.len()];
+    let cell = [n as f32, n as f32, 1., shear, 0., 0.];
+    GSDFrame::from_arrays(position, orientation, cell).into()
+}
+
+/// A lattice with different spacings along the x and y axes
+///
+/// The lattice is offset so no particles lie on the cell boundary.
+///
+pub fn rectangular_lattice(n: usize, spacing: [f32; 2]) -> Frame {
+    let length = [n as f32 * spacing[0], n as f32 * spacing[1]];
+    let position: Vec<[f32; 3]> = (0..n)
+        .flat_map(|y| {
+            (0..n).map(move |x| {
+                [
+                    (x as f32 + 0.5) * spacing[0] - length[0] / 2.,
+                    (y as f32 + 0.5) * spacing[1] - length[1] / 2.,
+                    0.,
+                ]
+            })
+        })
+        .collect();
+    let orientation = vec![[1., 0., 0., 0.]; position.len()];
+    let cell = [length[0], length[1], 1., 0., 0., 0.];
+    GSDFrame::from_arrays(position, orientation, cell).into()
+}
+
+/// A triangular lattice with a nearest neighbour distance of 1, with an optional perturbation
+pub fn triangular_lattice(n: usize, noise: f32) -> Frame {
+    let height = 3_f32.sqrt() / 2.;
+    let position: Vec<[f32; 3]> = (0..n)
+        .flat_map(|y| (0..n).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let offset = if y % 2 == 0 { 0. } else { 0.5 };
+            let i = (y * n + x) as f32;
+            [
+                x as f32 + offset + noise * (i * 1.7).sin(),
+                y as f32 * height + noise * (i * 2.3).cos(),
+                0.,
+            ]
+        })
+        .collect();
+    let orientation = vec![[1., 0., 0., 0.]; position.len()];
+    let cell = [n as f32, n as f32 * height, 1., 0., 0., 0.];
+    GSDFrame::from_arrays(position, orientation, cell).into()
+}
+
+/// Create a frame by repeating a basis of fractional coordinates within a unit cell
+pub fn crystal(basis: &[[f32; 3]], lengths: [f32; 3], repeats: [usize; 3], noise: f32) -> Frame {
+    let mut position = Vec::new();
+    for x in 0..repeats[0] {
+        for y in 0..repeats[1] {
+            for z in 0..repeats[2] {
+                for b in basis {
+                    let d = displacement(position.len(), noise);
+                    position.push([
+                        (x as f32 + b[0]) * lengths[0] + d[0],
+                        (y as f32 + b[1]) * lengths[1] + d[1],
+                        (z as f32 + b[2]) * lengths[2] + d[2],
+                    ]);
+                }
+            }
+        }
+    }
+    let cell = [
+        lengths[0] * repeats[0] as f32,
+        lengths[1] * repeats[1] as f32,
+        lengths[2] * repeats[2] as f32,
+        0.,
+        0.,
+        0.,
+    ];
+    let orientation = vec![[1., 0., 0., 0.]; position.len()];
+    GSDFrame::from_arrays(position, orientation, cell).into()
+}
+
+/// An FCC crystal with a nearest neighbour distance of 1
+pub fn fcc(noise: f32) -> Frame {
+    let basis = [[0., 0., 0.], [0.5, 0.5, 0.], [0.5, 0., 0.5], [0., 0.5, 0.5]];
+    let a = 2_f32.sqrt();
+    crystal(&basis, [a, a, a], [4, 4, 4], noise)
+}
+
+/// An HCP crystal with a nearest neighbour distance of 1, using an orthorhombic unit cell
+pub fn hcp(noise: f32) -> Frame {
+    let basis = [
+        [0., 0., 0.],
+        [0.5, 0.5, 0.],
+        [0.5, 5. / 6., 0.5],
+        [0., 1. / 3., 0.5],
+    ];
+    let lengths = [1., 3_f32.sqrt(), (8. / 3_f32).sqrt()];
+    crystal(&basis, lengths, [6, 4, 4], noise)
+}
@@ -7,7 +7,8 @@
 use crate::distance::make_cartesian;
 use crate::distance::min_image;
 use crate::frame::Frame;
-use anyhow::Error;
+use anyhow::{bail, Error};
+use nalgebra::Vector2;
 use std::convert::TryFrom;
 use voronoi::{make_polygons, voronoi, Cell, Point};
 
@@ -17,6 +18,91 @@ use voronoi::{make_polygons, voronoi, Cell, Point};
 /// within a Frame. Currently this doesn't take into account of the periodic boundary conditions.
 ///
 pub fn voronoi_area(frame: &Frame) -> Result<Vec<f64>, Error> {
+    Ok(voronoi_cells(frame)?.into_iter().map(shoelace).collect())
+}
+
+/// Compute the vertices of the voronoi cell surrounding each particle in a frame
+///
+/// The vertices of each cell are ordered around the perimeter, with any repeated vertices from
+/// the edges of zero length removed. Like [`voronoi_area`], the cells are bounded by the
+/// simulation cell rather than using the periodic boundary conditions.
+///
+pub fn voronoi_polygons(frame: &Frame) -> Result<Vec<Vec<[f64; 2]>>, Error> {
+    Ok(voronoi_cells(frame)?
+        .into_iter()
+        .map(|polygon| {
+            let vertices: Vec<[f64; 2]> = polygon.iter().map(|p| [p.x(), p.y()]).collect();
+            let size = vertices
+                .iter()
+                .flat_map(|a| vertices.iter().map(move |b| distance(a, b)))
+                .fold(0., f64::max);
+            vertices
+                .iter()
+                .enumerate()
+                .filter(|(i, v)| {
+                    let next = &vertices[(i + 1) % vertices.len()];
+                    distance(v, next) > f64::from(EDGE_TOLERANCE) * size
+                })
+                .map(|(_, v)| *v)
+                .collect()
+        })
+        .collect())
+}
+
+/// Compute the anisotropy of the voronoi cell surrounding each particle in a frame
+///
+/// This is the ratio of the largest to the smallest principal moment of the gyration tensor of
+/// the area of each cell,
+///
+/// $$ G_{\alpha\beta} = \frac{1}{A} \int (r_\alpha - c_\alpha)(r_\beta - c_\beta) \, dA $$
+///
+/// where $\mathbf{c}$ is the centroid of the cell. An isotropic cell, like a regular hexagon or
+/// a square, gives a value of 1, with the value increasing as the cell is stretched.
+///
+pub fn voronoi_anisotropy(frame: &Frame) -> Result<Vec<f64>, Error> {
+    Ok(voronoi_polygons(frame)?
+        .iter()
+        .map(|polygon| {
+            let [xx, xy, yy] = gyration_tensor(polygon);
+            // The eigenvalues of the symmetric 2x2 tensor
+            let mean = (xx + yy) / 2.;
+            let difference = (((xx - yy) / 2.).powi(2) + xy * xy).sqrt();
+            (mean + difference) / (mean - difference)
+        })
+        .collect())
+}
+
+/// The components xx, xy and yy of the gyration tensor of the area of a polygon
+///
+/// This uses the decomposition of the polygon into the triangles between the origin and each
+/// edge, where the signed areas ensure the result is independent of the winding direction.
+///
+fn gyration_tensor(polygon: &[[f64; 2]]) -> [f64; 3] {
+    let (mut area, mut cx, mut cy, mut ixx, mut ixy, mut iyy) = (0., 0., 0., 0., 0., 0.);
+    for (i, a) in polygon.iter().enumerate() {
+        let b = &polygon[(i + 1) % polygon.len()];
+        let cross = a[0] * b[1] - b[0] * a[1];
+        area += cross / 2.;
+        cx += (a[0] + b[0]) * cross / 6.;
+        cy += (a[1] + b[1]) * cross / 6.;
+        ixx += (a[0] * a[0] + a[0] * b[0] + b[0] * b[0]) * cross / 12.;
+        iyy += (a[1] * a[1] + a[1] * b[1] + b[1] * b[1]) * cross / 12.;
+        ixy += (2. * a[0] * a[1] + a[0] * b[1] + b[0] * a[1] + 2. * b[0] * b[1]) * cross / 24.;
+    }
+    let (cx, cy) = (cx / area, cy / area);
+    [
+        ixx / area - cx * cx,
+        ixy / area - cx * cy,
+        iyy / area - cy * cy,
+    ]
+}
+
+fn distance(a: &[f64; 2], b: &[f64; 2]) -> f64 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2)).sqrt()
+}
+
+/// The polygons of the voronoi diagram bounded by the simulation cell
+fn voronoi_cells(frame: &Frame) -> Result<Vec<Vec<Point>>, Error> {
     let points: Vec<Point> = frame
         .position
         .iter()
@@ -34,9 +120,168 @@ pub fn voronoi_area(frame: &Frame) -> Result<Vec<f64>, Error> {
 
     let boundary: Cell = Cell::try_from(cell_corners)?;
 
-    let polygons: Vec<_> = make_polygons(&voronoi(points, &boundary));
+    Ok(make_polygons(&voronoi(points, &boundary)))
+}
+
+/// Compute the local packing fraction of each particle from the area of its voronoi cell
+///
+/// This is the fraction of the voronoi cell covered by the particle, `particle_area` divided by
+/// the voronoi area, giving values which can be compared across state points. For a perfect
+/// crystal where each particle fills its voronoi cell this gives 1. Like [`voronoi_density`], a
+/// degenerate cell with zero area gives NaN.
+///
+pub fn voronoi_packing_fraction(frame: &Frame, particle_area: f64) -> Result<Vec<f64>, Error> {
+    if particle_area <= 0. {
+        bail!("The particle area must be positive, got {}", particle_area);
+    }
+    Ok(voronoi_area(frame)?
+        .into_iter()
+        .map(|area| {
+            if area > 0. {
+                particle_area / area
+            } else {
+                f64::NAN
+            }
+        })
+        .collect())
+}
+
+/// Compute the local density of each particle as the inverse of the area of its voronoi cell
+///
+/// A degenerate cell with zero area, as found where particles overlap, has a density of NaN
+/// rather than infinity.
+///
+pub fn voronoi_density(frame: &Frame) -> Result<Vec<f64>, Error> {
+    Ok(voronoi_area(frame)?
+        .into_iter()
+        .map(|area| if area > 0. { 1. / area } else { f64::NAN })
+        .collect())
+}
+
+/// Compute the free volume of each particle, the area of its voronoi cell not covered by itself
+///
+/// Each particle is treated as a disc with the corresponding diameter in `diameters`, with the
+/// area of the disc $\pi r^2$ subtracted from the voronoi area. Where the disc is larger than
+/// the cell, as for overlapping particles or a degenerate cell, the free volume is 0.
+///
+pub fn free_volume(frame: &Frame, diameters: &[f32]) -> Result<Vec<f64>, Error> {
+    if diameters.len() != frame.len() {
+        bail!(
+            "There needs to be a diameter for each of the {} particles, got {}",
+            frame.len(),
+            diameters.len()
+        );
+    }
+    Ok(voronoi_area(frame)?
+        .into_iter()
+        .zip(diameters)
+        .map(|(area, &diameter)| {
+            let radius = f64::from(diameter) / 2.;
+            (area - std::f64::consts::PI * radius * radius).max(0.)
+        })
+        .collect())
+}
+
+/// The number of nearest particles first considered as voronoi neighbours of each particle
+const VORONOI_CANDIDATES: usize = 16;
+
+/// Edges shorter than this fraction of the size of the voronoi cell are treated as zero length
+///
+/// This removes the spurious edges produced by floating point errors, as found in the degenerate
+/// case of a square lattice where four cells meet at a single vertex.
+const EDGE_TOLERANCE: f32 = 1e-4;
+
+/// A vertex of a voronoi cell, along with the neighbour sharing the edge from this vertex to the
+/// next, which is None for the edges of the initial bounding region.
+type Vertex = (Vector2<f32>, Option<usize>);
+
+/// Clip a convex polygon to the half of the plane closer to the origin than `point`
+fn clip_polygon(polygon: &[Vertex], point: &Vector2<f32>, neighbour: usize) -> Vec<Vertex> {
+    let limit = point.norm_squared() / 2.;
+    let inside = |v: &Vector2<f32>| v.dot(point) <= limit;
+    let intersection = |a: &Vector2<f32>, b: &Vector2<f32>| {
+        let t = (limit - a.dot(point)) / (b - a).dot(point);
+        a + (b - a) * t
+    };
 
-    Ok(polygons.into_iter().map(shoelace).collect())
+    let mut clipped = Vec::with_capacity(polygon.len() + 1);
+    for (i, &(a, label)) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()].0;
+        match (inside(&a), inside(&b)) {
+            (true, true) => clipped.push((a, label)),
+            (true, false) => {
+                clipped.push((a, label));
+                clipped.push((intersection(&a, &b), Some(neighbour)));
+            }
+            (false, true) => clipped.push((intersection(&a, &b), label)),
+            (false, false) => (),
+        }
+    }
+    clipped
+}
+
+/// Construct the voronoi cell of a particle from the `num_candidates` nearest particles
+///
+/// The vertices are relative to the particle, returning None when the candidates are not enough
+/// to be certain the cell is correct.
+///
+fn voronoi_cell(frame: &Frame, index: usize, num_candidates: usize) -> Option<Vec<Vertex>> {
+    let candidates: Vec<(usize, Vector2<f32>)> = frame
+        .particle_neighbours_n(index, num_candidates)
+        .map(|n| (n, frame.neighbour_vector(index, n).xy()))
+        .collect();
+    let furthest = candidates.iter().map(|(_, v)| v.norm()).fold(0., f32::max);
+
+    let mut cell: Vec<Vertex> = [[-1., -1.], [1., -1.], [1., 1.], [-1., 1.]]
+        .iter()
+        .map(|c| (Vector2::new(c[0], c[1]) * furthest, None))
+        .collect();
+    for (neighbour, point) in candidates.iter() {
+        cell = clip_polygon(&cell, point, *neighbour);
+    }
+
+    // Any particle which could affect the cell is within twice the distance of the furthest vertex
+    let radius = cell.iter().map(|(v, _)| v.norm()).fold(0., f32::max);
+    let complete = 2. * radius < furthest || candidates.len() + 1 >= frame.len();
+    if complete {
+        Some(cell)
+    } else {
+        None
+    }
+}
+
+/// Find the voronoi neighbours of each particle, along with the length of the shared edge
+///
+/// This constructs the voronoi cell of each particle in the xy plane, taking into account the
+/// periodic boundary conditions. Each neighbour is returned along with the length of the edge of
+/// the voronoi cell it shares with the particle, with edges of zero length excluded.
+///
+pub fn voronoi_neighbours(frame: &Frame) -> Vec<Vec<(usize, f32)>> {
+    (0..frame.len())
+        .map(|index| {
+            let mut num_candidates = VORONOI_CANDIDATES;
+            let cell = loop {
+                if let Some(cell) = voronoi_cell(frame, index, num_candidates) {
+                    break cell;
+                }
+                num_candidates *= 2;
+            };
+            let size = cell.iter().map(|(v, _)| v.norm()).fold(0., f32::max);
+            cell.iter()
+                .enumerate()
+                .filter_map(|(i, (a, label))| {
+                    let b = cell[(i + 1) % cell.len()].0;
+                    let length = (b - a).norm();
+                    match label {
+                        Some(neighbour) if length > EDGE_TOLERANCE * size => {
+                            Some((*neighbour, length))
+                        }
+                        _ => None,
+                    }
+                })
+                .collect()
+        })
+        .collect()
 }
 
 fn shoelace(polygon: Vec<Point>) -> f64 {
@@ -52,6 +297,8 @@ fn shoelace(polygon: Vec<Point>) -> f64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::test_utils::rectangular_lattice;
+    use approx::assert_abs_diff_eq;
     use voronoi::Point;
 
     #[test]
@@ -59,4 +306,116 @@ mod tests {
         let points = vec![Point::new(0., 1.), Point::new(2., 3.), Point::new(4., 7.)];
         assert_eq!(shoelace(points), 2.)
     }
+
+    #[test]
+    fn voronoi_neighbours_lattice() {
+        let frame = rectangular_lattice(6, [1.5, 1.5]);
+        let neighbours = voronoi_neighbours(&frame);
+        // The corner particle has neighbours across the periodic boundary
+        let mut corner: Vec<usize> = neighbours[0].iter().map(|(n, _)| *n).collect();
+        corner.sort_unstable();
+        assert_eq!(corner, vec![1, 5, 6, 30]);
+        for (_, length) in neighbours.iter().flatten() {
+            assert_abs_diff_eq!(*length, 1.5, epsilon = 1e-4);
+        }
+    }
+
+    #[test]
+    fn packing_fraction_lattice() -> Result<(), Error> {
+        let frame = rectangular_lattice(6, [1.5, 1.5]);
+        let fraction = voronoi_packing_fraction(&frame, 1.5)?;
+        assert_eq!(fraction.len(), 36);
+        // Each cell has an area of 2.25, which the particle covers 2/3 of
+        for value in fraction {
+            assert_abs_diff_eq!(value, 1.5 / 2.25, epsilon = 1e-5);
+        }
+        // Particles which fill their cells have a packing fraction of 1
+        for value in voronoi_packing_fraction(&frame, 2.25)? {
+            assert_abs_diff_eq!(value, 1., epsilon = 1e-5);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn polygons_lattice() -> Result<(), Error> {
+        let frame = rectangular_lattice(6, [1.5, 1.5]);
+        let polygons = voronoi_polygons(&frame)?;
+        assert_eq!(polygons.len(), 36);
+        for (position, polygon) in frame.position.iter().zip(polygons) {
+            // Only the interior cells are away from the boundary of the simulation cell
+            if position.x.abs() > 3. || position.y.abs() > 3. {
+                continue;
+            }
+            assert_eq!(polygon.len(), 4);
+            for (i, vertex) in polygon.iter().enumerate() {
+                let next = &polygon[(i + 1) % polygon.len()];
+                assert_abs_diff_eq!(distance(vertex, next), 1.5, epsilon = 1e-5);
+                // Each vertex is at the corner of the square surrounding the particle
+                assert_abs_diff_eq!((vertex[0] - position.x as f64).abs(), 0.75, epsilon = 1e-5);
+                assert_abs_diff_eq!((vertex[1] - position.y as f64).abs(), 0.75, epsilon = 1e-5);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn gyration_tensor_rectangle() {
+        // A rectangle has moments of a^2 / 12 and b^2 / 12, in either winding direction
+        let rectangle = [[0., 0.], [2., 0.], [2., 1.], [0., 1.]];
+        let expected = [4. / 12., 0., 1. / 12.];
+        for (value, e) in gyration_tensor(&rectangle).iter().zip(expected) {
+            assert_abs_diff_eq!(*value, e, epsilon = 1e-12);
+        }
+        let reversed: Vec<[f64; 2]> = rectangle.iter().rev().copied().collect();
+        for (value, e) in gyration_tensor(&reversed).iter().zip(expected) {
+            assert_abs_diff_eq!(*value, e, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn anisotropy_strained_lattice() -> Result<(), Error> {
+        let mean_anisotropy = |strain: f32| -> Result<f64, Error> {
+            let values = voronoi_anisotropy(&rectangular_lattice(6, [1. + strain, 1.]))?;
+            Ok(values.iter().sum::<f64>() / values.len() as f64)
+        };
+        assert_abs_diff_eq!(mean_anisotropy(0.)?, 1., epsilon = 1e-5);
+        // The rectangular cells have a ratio of moments of the square of the aspect ratio
+        assert_abs_diff_eq!(mean_anisotropy(0.2)?, 1.44, epsilon = 1e-4);
+        assert!(mean_anisotropy(0.1)? < mean_anisotropy(0.2)?);
+        Ok(())
+    }
+
+    #[test]
+    fn density_lattice() -> Result<(), Error> {
+        let frame = rectangular_lattice(6, [1.5, 1.5]);
+        let density = voronoi_density(&frame)?;
+        assert_eq!(density.len(), 36);
+        // Every cell of the lattice has the same area, giving the number density N / V
+        let expected = 36. / (9. * 9.);
+        for value in density {
+            assert_abs_diff_eq!(value, expected, epsilon = 1e-6);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn packing_fraction_invalid_area() {
+        let frame = rectangular_lattice(2, [1., 1.]);
+        assert!(voronoi_packing_fraction(&frame, 0.).is_err());
+    }
+
+    #[test]
+    fn free_volume_lattice() -> Result<(), Error> {
+        let frame = rectangular_lattice(6, [1.5, 1.5]);
+        let free = free_volume(&frame, &[1.; 36])?;
+        assert_eq!(free.len(), 36);
+        let expected = 1.5 * 1.5 - std::f64::consts::PI / 4.;
+        for value in free {
+            assert_abs_diff_eq!(value, expected, epsilon = 1e-5);
+        }
+        // Particles larger than their voronoi cell have no free volume
+        assert!(free_volume(&frame, &[2.; 36])?.iter().all(|&v| v == 0.));
+        assert!(free_volume(&frame, &[1.; 4]).is_err());
+        Ok(())
+    }
 }
@@ -0,0 +1,421 @@
+//
+// cli.rs
+// Copyright (C) 2019 Malcolm Ramsay <malramsay64@gmail.com>
+// Distributed under terms of the MIT license.
+//
+
+//! Run the command line interface on the trajectory used for testing
+
+use std::path::PathBuf;
+use std::process::Command;
+
+const TEST_FILE: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/trajectory.gsd");
+
+/// A path within the temporary directory which is unique to this test
+fn output_file(name: &str) -> PathBuf {
+    let mut filename = std::env::temp_dir();
+    filename.push(format!("trajedy-{}-{}", std::process::id(), name));
+    filename
+}
+
+fn trajedy() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_trajedy"))
+}
+
+#[test]
+fn train_writes_model() {
+    let model = output_file("model.json");
+    let status = trajedy()
+        .args(["train", TEST_FILE, "--index", "1", "--output"])
+        .arg(&model)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+    assert!(model.metadata().expect("Model file not written").len() > 0);
+    std::fs::remove_file(model).unwrap();
+}
+
+#[test]
+fn analyse_summary_rows() {
+    let outfile = output_file("summary.csv");
+    let status = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "3", "--summary"])
+        .arg(&outfile)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    // There is an additional row for the header
+    assert_eq!(contents.lines().count(), 3 + 1);
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_dump_features() {
+    let outfile = output_file("dump-output.csv");
+    let features = output_file("features.csv");
+    let status = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "2"])
+        .arg(&outfile)
+        .arg("--dump-features")
+        .arg(&features)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(&features).expect("Feature file not written");
+    let mut lines = contents.lines();
+    let header: Vec<&str> = lines.next().expect("Missing header").split(',').collect();
+    // The molecule and timestep columns along with the 6 features
+    assert_eq!(header.len(), 2 + 6);
+    // The test trajectory has 4032 particles in each frame
+    assert_eq!(lines.count(), 2 * 4032);
+
+    std::fs::remove_file(outfile).unwrap();
+    std::fs::remove_file(features).unwrap();
+}
+
+#[test]
+fn analyse_num_neighbours() {
+    let outfile = output_file("num-neighbours.csv");
+    let status = trajedy()
+        .args([
+            "analyse",
+            TEST_FILE,
+            "--num-frames",
+            "1",
+            "--num-neighbours",
+            "4",
+        ])
+        .arg(&outfile)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    let mut lines = contents.lines();
+    let header: Vec<&str> = lines.next().expect("Missing header").split(',').collect();
+    let columns: Vec<usize> = ["orient_order", "hexatic_order"]
+        .iter()
+        .map(|name| header.iter().position(|h| h == name).unwrap())
+        .collect();
+    for line in lines {
+        let values: Vec<&str> = line.split(',').collect();
+        for &column in columns.iter() {
+            let value: f32 = values[column].parse().unwrap();
+            assert!((0. ..=1.).contains(&value), "{} out of range", value);
+        }
+    }
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_precision() {
+    let outfile = output_file("precision.csv");
+    let status = trajedy()
+        .args([
+            "analyse",
+            TEST_FILE,
+            "--num-frames",
+            "1",
+            "--precision",
+            "3",
+        ])
+        .args(["--columns", "molecule,orient_order"])
+        .arg(&outfile)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("molecule,orient_order"));
+    let decimals: Vec<usize> = lines
+        .map(|line| {
+            let value = line.split(',').nth(1).expect("Missing orient_order column");
+            value.split('.').nth(1).map_or(0, str::len)
+        })
+        .collect();
+    assert_eq!(decimals.len(), 4032);
+    assert!(decimals.iter().all(|&d| d <= 3));
+    assert!(decimals.contains(&3));
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_timing() {
+    let outfile = output_file("timing.csv");
+    let output = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "2", "--timing"])
+        .arg(&outfile)
+        .output()
+        .expect("Running trajedy failed");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).expect("Output is not utf-8");
+    let frames = stdout.lines().filter(|l| l.starts_with("timing")).count();
+    assert_eq!(frames, 2);
+    assert!(stdout.lines().any(|l| l.starts_with("stage")));
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_metric_columns() {
+    let outfile = output_file("metrics.csv");
+    let status = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "1"])
+        .args(["--steinhardt", "4", "--steinhardt", "6"])
+        .arg(&outfile)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    let mut lines = contents.lines();
+    let header: Vec<&str> = lines.next().expect("Missing header").split(',').collect();
+    assert_eq!(header[header.len() - 2..], ["q4", "q6"]);
+    for line in lines {
+        assert_eq!(line.split(',').count(), header.len());
+    }
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_summary_metrics() {
+    let outfile = output_file("summary-metrics.csv");
+    let status = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "2", "--summary"])
+        .args(["--steinhardt", "6"])
+        .arg(&outfile)
+        .status()
+        .expect("Running trajedy failed");
+    assert!(status.success());
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    let mut lines = contents.lines();
+    assert_eq!(
+        lines.next(),
+        Some("timestep,orient_order,hexatic_order,liquid,p2,p2gg,pg,q6")
+    );
+    for line in lines {
+        let q6: f32 = line.split(',').nth(7).unwrap().parse().unwrap();
+        assert!((0. ..=1.).contains(&q6), "{} out of range", q6);
+    }
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_cached_model() {
+    let model = output_file("cached-model.json");
+    let run = |name: &str| {
+        let outfile = output_file(name);
+        let status = trajedy()
+            .args(["analyse", TEST_FILE, "--num-frames", "1"])
+            .args(["--training", TEST_FILE, "--training-index", "1", "--model"])
+            .arg(&model)
+            .arg(&outfile)
+            .status()
+            .expect("Running trajedy failed");
+        assert!(status.success());
+        let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+        std::fs::remove_file(outfile).unwrap();
+        contents
+    };
+
+    let trained = run("trained.csv");
+    let modified = model
+        .metadata()
+        .and_then(|m| m.modified())
+        .expect("Model file not written");
+    // The time taken by each run is dominated by the analysis of the frame, so rather than
+    // comparing the timing, the model being loaded is checked by the file being left unchanged.
+    let loaded = run("loaded.csv");
+    assert_eq!(
+        model.metadata().and_then(|m| m.modified()).unwrap(),
+        modified
+    );
+    assert_eq!(trained, loaded);
+    std::fs::remove_file(model).unwrap();
+}
+
+#[test]
+fn analyse_missing_model() {
+    let model = output_file("missing-model.json");
+    let status = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "1", "--model"])
+        .arg(&model)
+        .arg(output_file("missing-model.csv"))
+        .status()
+        .expect("Running trajedy failed");
+    assert!(!status.success());
+}
+
+#[test]
+fn analyse_append() {
+    let outfile = output_file("append.csv");
+    for _ in 0..2 {
+        let status = trajedy()
+            .args(["analyse", TEST_FILE, "--num-frames", "2", "--append"])
+            .arg(&outfile)
+            .status()
+            .expect("Running trajedy failed");
+        assert!(status.success());
+    }
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    let header = contents.lines().next().expect("Missing header");
+    assert_eq!(contents.lines().filter(|&l| l == header).count(), 1);
+    // The test trajectory has 4032 particles in each frame
+    assert_eq!(contents.lines().count(), 2 * 2 * 4032 + 1);
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_append_summary() {
+    let outfile = output_file("append-summary.csv");
+    for _ in 0..2 {
+        let status = trajedy()
+            .args([
+                "analyse",
+                TEST_FILE,
+                "--num-frames",
+                "3",
+                "--summary",
+                "--append",
+            ])
+            .arg(&outfile)
+            .status()
+            .expect("Running trajedy failed");
+        assert!(status.success());
+    }
+
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    assert_eq!(contents.lines().count(), 2 * 3 + 1);
+    std::fs::remove_file(outfile).unwrap();
+}
+
+#[test]
+fn analyse_small_buffer() {
+    let run = |buffer: &str, name: &str| {
+        let outfile = output_file(name);
+        let status = trajedy()
+            .args([
+                "analyse",
+                TEST_FILE,
+                "--num-frames",
+                "4",
+                "--buffer",
+                buffer,
+            ])
+            .arg(&outfile)
+            .status()
+            .expect("Running trajedy failed");
+        assert!(status.success());
+        let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+        std::fs::remove_file(outfile).unwrap();
+        contents
+    };
+    let small = run("1", "buffer-small.csv");
+    let large = run("64", "buffer-large.csv");
+    // The test trajectory has 4032 particles in each frame
+    assert_eq!(small.lines().count(), 4 * 4032 + 1);
+
+    // The frames can be written in a different order when analysed in parallel
+    let mut small: Vec<&str> = small.lines().collect();
+    let mut large: Vec<&str> = large.lines().collect();
+    small.sort_unstable();
+    large.sort_unstable();
+    assert_eq!(small, large);
+}
+
+#[test]
+fn analyse_classify_only() {
+    let outfile = output_file("classify-only.csv");
+    let output = trajedy()
+        .args(["analyse", TEST_FILE, "--num-frames", "2", "--timing"])
+        .arg("--classify-only")
+        .arg(&outfile)
+        .output()
+        .expect("Running trajedy failed");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).expect("Output is not utf-8");
+    // The total time of the order stage from the timing summary
+    let order_time: f64 = stdout
+        .lines()
+        .find(|l| l.starts_with("order "))
+        .and_then(|l| l.split_whitespace().nth(1))
+        .expect("Missing order stage")
+        .parse()
+        .unwrap();
+    let contents = std::fs::read_to_string(&outfile).expect("Output file not written");
+    std::fs::remove_file(outfile).unwrap();
+
+    let mut lines = contents.lines();
+    assert_eq!(lines.next(), Some("molecule,timestep,class"));
+    assert_eq!(lines.count(), 2 * 4032);
+    // Skipping the order parameters leaves no work in the order stage, which takes milliseconds
+    // to compute for this trajectory
+    assert!(order_time < 0.1, "Order stage took {} ms", order_time);
+}
+
+#[test]
+fn analyse_non_finite_frame() {
+    let infile = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/non-finite.gsd");
+    let outfile = output_file("non-finite.csv");
+    let output = trajedy()
+        .args(["analyse", infile])
+        .arg(&outfile)
+        .output()
+        .expect("Running trajedy failed");
+    std::fs::remove_file(outfile).unwrap_or(());
+    // The second frame has a NaN position for the particle with index 2
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("Output is not utf-8");
+    assert!(stderr.contains("Analysing frame 1 failed"), "{}", stderr);
+    assert!(stderr.contains("[2]"), "{}", stderr);
+    assert!(!stderr.contains("panicked"), "{}", stderr);
+}
+
+#[test]
+fn analyse_unreadable_frame() {
+    let infile = concat!(env!("CARGO_MANIFEST_DIR"), "/gsd/tests/mixed-step.gsd");
+    let outfile = output_file("unreadable.csv");
+    let output = trajedy()
+        .args(["analyse", infile])
+        .arg(&outfile)
+        .output()
+        .expect("Running trajedy failed");
+    std::fs::remove_file(outfile).unwrap_or(());
+    // Only the second frame is missing the timestep, which can't be read
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("Output is not utf-8");
+    assert!(stderr.contains("Reading frame 1 failed"), "{}", stderr);
+}
+
+#[test]
+fn analyse_append_mismatch() {
+    let outfile = output_file("append-mismatch.csv");
+    let run = |summary: bool| {
+        let mut command = trajedy();
+        command.args(["analyse", TEST_FILE, "--num-frames", "1", "--append"]);
+        if summary {
+            command.arg("--summary");
+        }
+        command
+            .arg(&outfile)
+            .output()
+            .expect("Running trajedy failed")
+    };
+    assert!(run(false).status.success());
+    let before = std::fs::read_to_string(&outfile).expect("Output file not written");
+
+    // The summary has different columns to the rows already in the file
+    let output = run(true);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).expect("Output is not utf-8");
+    assert!(stderr.contains("Unable to append"), "{}", stderr);
+    let after = std::fs::read_to_string(&outfile).expect("Output file removed");
+    assert_eq!(before, after);
+    std::fs::remove_file(outfile).unwrap();
+}